@@ -0,0 +1,722 @@
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use clap::builder::{TypedValueParser, ValueParser};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum, ValueHint};
+
+/// The character typically used to separate path components
+/// in environment variables.
+const ENV_PATH_SEP: char = if cfg!(windows) { ';' } else { ':' };
+
+/// A small Typst compiler and previewer.
+#[derive(Debug, Clone, Parser)]
+#[clap(name = "main", version, max_term_width = 80)]
+pub struct CliArguments {
+    /// The command to run.
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Logs what the compiler and the preview are doing to stderr. Repeat to
+    /// log in more detail (`-vv` for debug and `-vvv` for trace messages).
+    ///
+    /// The `RUST_LOG` environment variable takes precedence and accepts
+    /// filters like `main::gui=debug`.
+    #[clap(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Logs nothing, not even warnings. Diagnostics are still printed.
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+}
+
+/// What to do.
+#[derive(Debug, Clone, Subcommand)]
+#[command()]
+pub enum Command {
+    /// Compiles an input file into a supported output format (PDF by default).
+    #[command(visible_alias = "c")]
+    Compile(CompileCommand),
+
+    /// Compiles an input file into one or multiple images (PNG by default).
+    Image(CompileCommand),
+
+    /// Compiles an input file into an HTML document.
+    Html(CompileCommand),
+
+    /// Watches an input file and recompiles on changes.
+    #[command(visible_alias = "w")]
+    Watch(WatchCommand),
+
+    /// Opens an input file in the preview window.
+    Render(RenderCommand),
+
+    /// Processes an input file to extract provided metadata.
+    Query(QueryCommand),
+
+    /// Lists all discovered fonts in system and custom font paths.
+    Fonts(FontsCommand),
+}
+
+/// Compiles an input file into a supported output format.
+#[derive(Debug, Clone, Parser)]
+pub struct CompileCommand {
+    /// Arguments for compilation.
+    #[clap(flatten)]
+    pub args: CompileArgs,
+}
+
+/// Watches an input file and recompiles on changes.
+#[derive(Debug, Clone, Parser)]
+pub struct WatchCommand {
+    /// Arguments for compilation.
+    #[clap(flatten)]
+    pub args: CompileArgs,
+}
+
+/// Opens an input file in the preview window.
+#[derive(Debug, Clone, Parser)]
+pub struct RenderCommand {
+    /// Path to input Typst file.
+    #[clap(default_value = "main.typ", value_hint = ValueHint::FilePath)]
+    pub input: PathBuf,
+
+    /// Which pages to show. When unspecified, all pages are shown.
+    ///
+    /// Uses the same syntax as the `--pages` argument of `compile`.
+    #[arg(long = "pages", value_delimiter = ',')]
+    pub pages: Option<Vec<Pages>>,
+
+    /// World arguments.
+    #[clap(flatten)]
+    pub world: WorldArgs,
+
+    /// Processing arguments.
+    #[clap(flatten)]
+    pub process: ProcessArgs,
+}
+
+/// Processes an input file to extract provided metadata.
+#[derive(Debug, Clone, Parser)]
+pub struct QueryCommand {
+    /// Path to input Typst file. Use `-` to read input from stdin.
+    #[clap(value_parser = input_value_parser(), value_hint = ValueHint::FilePath)]
+    pub input: Input,
+
+    /// Defines which elements to retrieve, e.g. `<label>`, `heading.where(level:
+    /// 1)` or `metadata`.
+    pub selector: String,
+
+    /// Extracts just one field from all retrieved elements, e.g. `value` for
+    /// metadata. Elements without the field are skipped.
+    #[clap(long = "field")]
+    pub field: Option<String>,
+
+    /// Expects and retrieves exactly one element.
+    #[clap(long = "one")]
+    pub one: bool,
+
+    /// The format to print the retrieved elements in.
+    #[clap(long = "format", default_value_t)]
+    pub format: QueryFormat,
+
+    /// World arguments.
+    #[clap(flatten)]
+    pub world: WorldArgs,
+
+    /// Processing arguments.
+    #[clap(flatten)]
+    pub process: ProcessArgs,
+}
+
+/// Lists all discovered fonts in system and custom font paths.
+#[derive(Debug, Clone, Parser)]
+pub struct FontsCommand {
+    /// Common font arguments.
+    #[clap(flatten)]
+    pub font: FontArgs,
+
+    /// Also lists the style variants of each font family, along with the file
+    /// they are loaded from and their names.
+    #[arg(long)]
+    pub variants: bool,
+
+    /// Only lists fonts that have glyphs for all characters of the text,
+    /// ignoring whitespace.
+    #[arg(long, value_name = "TEXT")]
+    pub covers: Option<String>,
+}
+
+/// Arguments for compilation and watching.
+#[derive(Debug, Clone, Args)]
+pub struct CompileArgs {
+    /// Path to input Typst file. Use `-` to read input from stdin.
+    #[clap(value_parser = input_value_parser(), value_hint = ValueHint::FilePath)]
+    pub input: Input,
+
+    /// Path to output file (PDF, PNG, SVG, or HTML). Use `-` to write output to
+    /// stdout.
+    ///
+    /// For output formats emitting one file per page (PNG & SVG), a page number
+    /// template must be present if the source document renders to multiple
+    /// pages. Use `{p}` for page numbers, `{0p}` for zero padded page numbers
+    /// and `{t}` for page count. For example, `page-{0p}-of-{t}.png` creates
+    /// `page-01-of-10.png`, `page-02-of-10.png`, and so on.
+    #[clap(
+        short = 'o',
+        long = "output",
+        required_if_eq("input", "-"),
+        value_parser = output_value_parser(),
+        value_hint = ValueHint::FilePath,
+    )]
+    pub output: Option<Output>,
+
+    /// The format of the output file, inferred from the extension by default.
+    #[arg(long = "format", short = 'f')]
+    pub format: Option<OutputFormat>,
+
+    /// World arguments.
+    #[clap(flatten)]
+    pub world: WorldArgs,
+
+    /// Which pages to export. When unspecified, all pages are exported.
+    ///
+    /// Pages to export are separated by commas, and can be either simple page
+    /// numbers (e.g. '2,5' to export only pages 2 and 5) or page ranges (e.g.
+    /// '2,3-6,8-' to export page 2, pages 3 to 6 (inclusive), page 8 and any
+    /// pages after it).
+    ///
+    /// Page numbers are one-indexed and correspond to physical page numbers in
+    /// the document (therefore not being affected by the document's page
+    /// counter).
+    #[arg(long = "pages", value_delimiter = ',')]
+    pub pages: Option<Vec<Pages>>,
+
+    /// One (or multiple comma-separated) PDF standards that Typst will enforce
+    /// conformance with.
+    #[arg(long = "pdf-standard", value_delimiter = ',')]
+    pub pdf_standard: Vec<PdfStandard>,
+
+    /// Writes a tagged PDF document, which describes the structure of the
+    /// content to assistive technology such as screen readers.
+    ///
+    /// Tags are always written for standards that require them (PDF/A-1a,
+    /// PDF/A-2a, PDF/A-3a and PDF/UA-1). They cannot be written when only some
+    /// of the pages are exported with `--pages`.
+    #[arg(long = "pdf-tags")]
+    pub pdf_tags: bool,
+
+    /// The PPI (pixels per inch) to use for PNG export.
+    #[arg(long = "ppi", default_value_t = 120.0)]
+    pub ppi: f32,
+
+    /// File path to which a list of current compilation's dependencies will be
+    /// written. Use `-` to write to stdout.
+    #[clap(
+        long,
+        value_name = "PATH",
+        value_parser = output_value_parser(),
+        value_hint = ValueHint::FilePath,
+    )]
+    pub deps: Option<Output>,
+
+    /// File format to use for dependencies.
+    #[clap(long, default_value_t)]
+    pub deps_format: DepsFormat,
+
+    /// Processing arguments.
+    #[clap(flatten)]
+    pub process: ProcessArgs,
+
+    /// Records timings of the compilation into a JSON file in the Chrome trace
+    /// format.
+    ///
+    /// The trace covers loading files, evaluation, realization, each layout
+    /// iteration and the export. In watch mode, each compilation is recorded
+    /// separately and the path must contain `{n}`, which is replaced with the
+    /// number of the compilation.
+    ///
+    /// The file can be loaded into a tracing tool such as
+    /// https://ui.perfetto.dev. It contains no sensitive information apart
+    /// from file names and line numbers.
+    #[arg(long = "timings", value_name = "OUTPUT_JSON")]
+    pub timings: Option<PathBuf>,
+}
+
+/// Arguments for the construction of a world. Shared by compile, render, and
+/// query.
+#[derive(Debug, Clone, Args)]
+pub struct WorldArgs {
+    /// Configures the project root (for absolute paths).
+    #[clap(long = "root", env = "TYPST_ROOT", value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Add a string key-value pair visible through `sys.inputs`.
+    #[clap(
+        long = "input",
+        value_name = "key=value",
+        action = ArgAction::Append,
+        value_parser = ValueParser::new(parse_sys_input_pair),
+    )]
+    pub inputs: Vec<(String, String)>,
+
+    /// Common font arguments.
+    #[clap(flatten)]
+    pub font: FontArgs,
+
+    /// Arguments related to storage of packages in the system.
+    #[clap(flatten)]
+    pub package: PackageArgs,
+
+    /// The document's creation date formatted as a UNIX timestamp.
+    ///
+    /// It is used as the date of `datetime.today()`, in UTC unless an offset
+    /// is given, and as the creation date in the PDF metadata. For more
+    /// information, see
+    /// <https://reproducible-builds.org/specs/source-date-epoch/>.
+    #[clap(
+        long = "creation-timestamp",
+        env = "SOURCE_DATE_EPOCH",
+        value_name = "UNIX_TIMESTAMP",
+        value_parser = parse_source_date_epoch,
+    )]
+    pub creation_timestamp: Option<DateTime<Utc>>,
+}
+
+/// Arguments for configuration the process of compilation itself.
+#[derive(Debug, Clone, Args)]
+pub struct ProcessArgs {
+    /// Number of parallel jobs spawned during compilation. Defaults to number
+    /// of CPUs. Setting it to 1 disables parallelism.
+    #[clap(long, short)]
+    pub jobs: Option<usize>,
+
+    /// The format to emit diagnostics in.
+    #[clap(long, default_value_t)]
+    pub diagnostic_format: DiagnosticFormat,
+}
+
+/// Arguments related to where packages are stored in the system.
+#[derive(Debug, Clone, Default, Args)]
+pub struct PackageArgs {
+    /// Custom path to local packages, defaults to system-dependent location.
+    #[clap(long = "package-path", env = "TYPST_PACKAGE_PATH", value_name = "DIR")]
+    pub package_path: Option<PathBuf>,
+
+    /// Custom path to package cache, defaults to system-dependent location.
+    #[clap(
+        long = "package-cache-path",
+        env = "TYPST_PACKAGE_CACHE_PATH",
+        value_name = "DIR"
+    )]
+    pub package_cache_path: Option<PathBuf>,
+
+    /// Directory to fetch packages from instead of the network. It must be
+    /// laid out like the package path, i.e. `{namespace}/{name}/{version}`.
+    #[clap(
+        long = "package-registry",
+        env = "TYPST_PACKAGE_REGISTRY",
+        value_name = "DIR"
+    )]
+    pub package_registry: Option<PathBuf>,
+}
+
+/// Common arguments to customize available fonts.
+#[derive(Debug, Clone, Args)]
+pub struct FontArgs {
+    /// Adds additional directories that are recursively searched for fonts.
+    ///
+    /// If multiple paths are specified, they are separated by the system's path
+    /// separator (`:` on Unix-like systems and `;` on Windows).
+    #[clap(
+        long = "font-path",
+        env = "TYPST_FONT_PATHS",
+        value_name = "DIR",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub font_paths: Vec<PathBuf>,
+
+    /// Ensures system fonts won't be searched, unless explicitly included via
+    /// `--font-path`.
+    #[arg(long, env = "TYPST_IGNORE_SYSTEM_FONTS")]
+    pub ignore_system_fonts: bool,
+
+    /// Ensures fonts embedded into Typst won't be considered.
+    #[arg(long, env = "TYPST_IGNORE_EMBEDDED_FONTS")]
+    pub ignore_embedded_fonts: bool,
+
+    /// Uses exactly the fonts listed in a TOML manifest, so that the output is
+    /// the same on every machine.
+    ///
+    /// The fonts that the manifest resolves to are recorded in a lockfile next
+    /// to it, which later runs must match. The other font options are ignored.
+    #[arg(long, env = "TYPST_FONT_MANIFEST", value_name = "FILE")]
+    pub font_manifest: Option<PathBuf>,
+}
+
+macro_rules! display_possible_values {
+    ($ty:ty) => {
+        impl Display for $ty {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                self.to_possible_value()
+                    .expect("no values are skipped")
+                    .get_name()
+                    .fmt(f)
+            }
+        }
+    };
+}
+
+/// An input that is either stdin or a real path.
+#[derive(Debug, Clone)]
+pub enum Input {
+    /// Stdin, represented by `-`.
+    Stdin,
+    /// A non-empty path.
+    Path(PathBuf),
+}
+
+impl Display for Input {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Stdin => f.pad("stdin"),
+            Input::Path(path) => path.display().fmt(f),
+        }
+    }
+}
+
+/// An output that is either stdout or a real path.
+#[derive(Debug, Clone)]
+pub enum Output {
+    /// Stdout, represented by `-`.
+    Stdout,
+    /// A non-empty path.
+    Path(PathBuf),
+}
+
+impl Output {
+    /// Write data to the output.
+    pub fn write(&self, buffer: &[u8]) -> std::io::Result<()> {
+        match self {
+            Output::Stdout => std::io::stdout().write_all(buffer),
+            Output::Path(path) => std::fs::write(path, buffer),
+        }
+    }
+
+    /// Open the output for writing.
+    pub fn open(&self) -> std::io::Result<OpenOutput<'_>> {
+        match self {
+            Self::Stdout => Ok(OpenOutput::Stdout(std::io::stdout().lock())),
+            Self::Path(path) => std::fs::File::create(path).map(OpenOutput::File),
+        }
+    }
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Stdout => f.pad("stdout"),
+            Output::Path(path) => path.display().fmt(f),
+        }
+    }
+}
+
+/// A step-by-step writable version of [`Output`].
+#[derive(Debug)]
+pub enum OpenOutput<'a> {
+    Stdout(std::io::StdoutLock<'a>),
+    File(std::fs::File),
+}
+
+impl Write for OpenOutput<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OpenOutput::Stdout(v) => v.write(buf),
+            OpenOutput::File(v) => v.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OpenOutput::Stdout(v) => v.flush(),
+            OpenOutput::File(v) => v.flush(),
+        }
+    }
+}
+
+/// Which format to use for the generated output file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, ValueEnum)]
+pub enum OutputFormat {
+    Pdf,
+    Png,
+    Svg,
+    Html,
+}
+
+display_possible_values!(OutputFormat);
+
+/// A PDF standard that Typst can enforce conformance with.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+#[allow(non_camel_case_types)]
+pub enum PdfStandard {
+    /// PDF 1.4.
+    #[value(name = "1.4")]
+    V_1_4,
+    /// PDF 1.5.
+    #[value(name = "1.5")]
+    V_1_5,
+    /// PDF 1.6.
+    #[value(name = "1.6")]
+    V_1_6,
+    /// PDF 1.7.
+    #[value(name = "1.7")]
+    V_1_7,
+    /// PDF 2.0.
+    #[value(name = "2.0")]
+    V_2_0,
+    /// PDF/A-1b.
+    #[value(name = "a-1b")]
+    A_1b,
+    /// PDF/A-1a.
+    #[value(name = "a-1a")]
+    A_1a,
+    /// PDF/A-2b.
+    #[value(name = "a-2b")]
+    A_2b,
+    /// PDF/A-2u.
+    #[value(name = "a-2u")]
+    A_2u,
+    /// PDF/A-2a.
+    #[value(name = "a-2a")]
+    A_2a,
+    /// PDF/A-3b.
+    #[value(name = "a-3b")]
+    A_3b,
+    /// PDF/A-3u.
+    #[value(name = "a-3u")]
+    A_3u,
+    /// PDF/A-3a.
+    #[value(name = "a-3a")]
+    A_3a,
+    /// PDF/A-4.
+    #[value(name = "a-4")]
+    A_4,
+    /// PDF/A-4f.
+    #[value(name = "a-4f")]
+    A_4f,
+    /// PDF/A-4e.
+    #[value(name = "a-4e")]
+    A_4e,
+    /// PDF/UA-1.
+    #[value(name = "ua-1")]
+    UA_1,
+}
+
+display_possible_values!(PdfStandard);
+
+impl OutputFormat {
+    /// The file extension conventionally used for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Png => "png",
+            OutputFormat::Svg => "svg",
+            OutputFormat::Html => "html",
+        }
+    }
+}
+
+/// Which format to use for a generated dependency file.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum DepsFormat {
+    /// Encodes as JSON, failing for non-Unicode paths.
+    #[default]
+    Json,
+    /// Separates paths with NULL bytes and can express all paths.
+    Zero,
+    /// Emits in Make format, omitting inexpressible paths.
+    Make,
+}
+
+display_possible_values!(DepsFormat);
+
+/// Which format to use for diagnostics.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, ValueEnum)]
+pub enum DiagnosticFormat {
+    /// Full messages with source snippets, hints and traces.
+    #[default]
+    Human,
+    /// One line per message, in the style of `file:line:column: message`.
+    Short,
+    /// One JSON object per message, with resolved file locations.
+    Json,
+}
+
+display_possible_values!(DiagnosticFormat);
+
+/// Which format to print query results in.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, ValueEnum)]
+pub enum QueryFormat {
+    /// A pretty-printed JSON array, or a single value with `--one`.
+    #[default]
+    Json,
+    /// A YAML sequence, or a single value with `--one`.
+    Yaml,
+    /// One value per line. Strings are printed as they are, content as its
+    /// plain text and everything else in Typst syntax.
+    Plain,
+}
+
+display_possible_values!(QueryFormat);
+
+/// Implements parsing of page ranges (`1-3`, `4`, `5-`, `-2`), used by the
+/// `CompileArgs.pages` argument, through the `FromStr` trait instead of a
+/// value parser, in order to generate better errors.
+///
+/// See also: https://github.com/clap-rs/clap/issues/5065
+#[derive(Debug, Clone)]
+pub struct Pages(pub RangeInclusive<Option<NonZeroUsize>>);
+
+impl FromStr for Pages {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value
+            .split('-')
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [] | [""] => Err("page export range must not be empty"),
+            [single_page] => {
+                let page_number = parse_page_number(single_page)?;
+                Ok(Pages(Some(page_number)..=Some(page_number)))
+            }
+            ["", ""] => Err("page export range must have start or end"),
+            [start, ""] => Ok(Pages(Some(parse_page_number(start)?)..=None)),
+            ["", end] => Ok(Pages(None..=Some(parse_page_number(end)?))),
+            [start, end] => {
+                let start = parse_page_number(start)?;
+                let end = parse_page_number(end)?;
+                if start > end {
+                    Err("page export range must end at a page after the start")
+                } else {
+                    Ok(Pages(Some(start)..=Some(end)))
+                }
+            }
+            [_, _, _, ..] => Err("page export range must have a single hyphen"),
+        }
+    }
+}
+
+impl Display for Pages {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.0.start(), self.0.end()) {
+            (Some(start), Some(end)) if start == end => write!(f, "{start}"),
+            (Some(start), Some(end)) => write!(f, "{start}-{end}"),
+            (Some(start), None) => write!(f, "{start}-"),
+            (None, Some(end)) => write!(f, "-{end}"),
+            (None, None) => f.pad("-"),
+        }
+    }
+}
+
+/// Parses a single page number.
+fn parse_page_number(value: &str) -> Result<NonZeroUsize, &'static str> {
+    if value == "0" {
+        Err("page numbers start at one")
+    } else {
+        NonZeroUsize::from_str(value).map_err(|_| "not a valid page number")
+    }
+}
+
+/// The clap value parser used by `CompileArgs.input`
+fn input_value_parser() -> impl TypedValueParser<Value = Input> {
+    clap::builder::OsStringValueParser::new().try_map(|value| {
+        if value.is_empty() {
+            Err(clap::Error::new(clap::error::ErrorKind::InvalidValue))
+        } else if value == "-" {
+            Ok(Input::Stdin)
+        } else {
+            Ok(Input::Path(value.into()))
+        }
+    })
+}
+
+/// The clap value parser used by `CompileArgs.output`
+fn output_value_parser() -> impl TypedValueParser<Value = Output> {
+    clap::builder::OsStringValueParser::new().try_map(|value| {
+        // Empty value also handled by clap for `Option<Output>`
+        if value.is_empty() {
+            Err(clap::Error::new(clap::error::ErrorKind::InvalidValue))
+        } else if value == "-" {
+            Ok(Output::Stdout)
+        } else {
+            Ok(Output::Path(value.into()))
+        }
+    })
+}
+
+/// Parses key/value pairs split by the first equal sign.
+///
+/// This function will return an error if the argument contains no equals sign
+/// or contains the key (before the equals sign) is empty.
+fn parse_sys_input_pair(raw: &str) -> Result<(String, String), String> {
+    let (key, val) = raw
+        .split_once('=')
+        .ok_or("input must be a key and a value separated by an equal sign")?;
+    let key = key.trim().to_owned();
+    if key.is_empty() {
+        return Err("the key was missing or empty".to_owned());
+    }
+    let val = val.trim().to_owned();
+    Ok((key, val))
+}
+
+/// Parses a UNIX timestamp according to <https://reproducible-builds.org/specs/source-date-epoch/>
+fn parse_source_date_epoch(raw: &str) -> Result<DateTime<Utc>, String> {
+    let timestamp: i64 = raw
+        .parse()
+        .map_err(|err| format!("timestamp must be decimal integer ({err})"))?;
+    DateTime::from_timestamp(timestamp, 0).ok_or_else(|| "timestamp out of range".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Result<String, &'static str> {
+        value.parse::<Pages>().map(|pages| pages.to_string())
+    }
+
+    #[test]
+    fn test_parse_pages() {
+        assert_eq!(parse("4"), Ok("4".into()));
+        assert_eq!(parse("1-3"), Ok("1-3".into()));
+        assert_eq!(parse(" 2 - 2 "), Ok("2".into()));
+        assert_eq!(parse("5-"), Ok("5-".into()));
+        assert_eq!(parse("-3"), Ok("-3".into()));
+    }
+
+    #[test]
+    fn test_parse_invalid_pages() {
+        assert_eq!(parse(""), Err("page export range must not be empty"));
+        assert_eq!(parse("-"), Err("page export range must have start or end"));
+        assert_eq!(parse("0"), Err("page numbers start at one"));
+        assert_eq!(parse("0-2"), Err("page numbers start at one"));
+        assert_eq!(parse("-0"), Err("page numbers start at one"));
+        assert_eq!(
+            parse("3-1"),
+            Err("page export range must end at a page after the start")
+        );
+        assert_eq!(
+            parse("1-2-3"),
+            Err("page export range must have a single hyphen")
+        );
+        assert_eq!(parse("one"), Err("not a valid page number"));
+    }
+}
@@ -0,0 +1,228 @@
+use std::ffi::OsString;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::SystemWorld;
+use crate::args::{DepsFormat, Output};
+
+/// Writes dependencies in the given format.
+///
+/// The outputs are `None` if the compilation failed. In that case, Make
+/// dependencies are not written at all since there is no rule to emit.
+pub fn write_deps(
+    world: &mut SystemWorld,
+    dest: &Output,
+    format: DepsFormat,
+    outputs: Option<&[Output]>,
+) -> io::Result<()> {
+    match format {
+        DepsFormat::Json => write_deps_json(world, dest, outputs)?,
+        DepsFormat::Zero => write_deps_zero(world, dest)?,
+        DepsFormat::Make => {
+            if let Some(outputs) = outputs {
+                write_deps_make(world, dest, outputs)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes dependencies in JSON format.
+fn write_deps_json(
+    world: &mut SystemWorld,
+    dest: &Output,
+    outputs: Option<&[Output]>,
+) -> io::Result<()> {
+    let inputs = relative_dependencies(world)?
+        .map(into_string)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let outputs = outputs
+        .map(|outputs| {
+            outputs
+                .iter()
+                .filter_map(|output| match output {
+                    Output::Path(path) => Some(into_string(path.clone().into_os_string())),
+                    Output::Stdout => None,
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+
+    #[derive(Serialize)]
+    struct Deps {
+        inputs: Vec<String>,
+        outputs: Option<Vec<String>>,
+    }
+
+    serde_json::to_writer(dest.open()?, &Deps { inputs, outputs })?;
+
+    Ok(())
+}
+
+/// Converts a path to a string, failing for non-Unicode paths.
+fn into_string(path: OsString) -> io::Result<String> {
+    path.into_string().map_err(|path| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{path:?} is not valid utf-8"),
+        )
+    })
+}
+
+/// Writes dependencies in the Zero / Text0 format.
+fn write_deps_zero(world: &mut SystemWorld, dest: &Output) -> io::Result<()> {
+    let mut dest = dest.open()?;
+    for dep in relative_dependencies(world)? {
+        dest.write_all(dep.as_encoded_bytes())?;
+        dest.write_all(b"\0")?;
+    }
+    Ok(())
+}
+
+/// Writes dependencies in the Make format.
+fn write_deps_make(world: &mut SystemWorld, dest: &Output, outputs: &[Output]) -> io::Result<()> {
+    let mut dest = dest.open()?;
+    for (i, output) in outputs.iter().enumerate() {
+        let path = match output {
+            Output::Path(path) => path.as_os_str(),
+            Output::Stdout => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "make dependencies contain the output path, \
+                     but the output was stdout",
+                ));
+            }
+        };
+
+        // Silently skip paths that aren't valid Unicode so we still
+        // produce a rule that will work for the other paths that can be
+        // processed.
+        let Some(string) = path.to_str() else {
+            continue;
+        };
+        if i != 0 {
+            dest.write_all(b" ")?;
+        }
+        dest.write_all(munge(string).as_bytes())?;
+    }
+    dest.write_all(b":")?;
+
+    for dep in relative_dependencies(world)? {
+        // See above.
+        let Some(string) = dep.to_str() else { continue };
+        dest.write_all(b" ")?;
+        dest.write_all(munge(string).as_bytes())?;
+    }
+    dest.write_all(b"\n")?;
+
+    Ok(())
+}
+
+// Based on `munge` in libcpp/mkdeps.cc from the GCC source code. This isn't
+// perfect as some special characters can't be escaped.
+fn munge(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut slashes = 0;
+    for c in s.chars() {
+        match c {
+            '\\' => slashes += 1,
+            '$' => {
+                res.push('$');
+                slashes = 0;
+            }
+            ':' => {
+                res.push('\\');
+                slashes = 0;
+            }
+            ' ' | '\t' => {
+                // `munge`'s source contains a comment here that says: "A
+                // space or tab preceded by 2N+1 backslashes represents N
+                // backslashes followed by space..."
+                for _ in 0..slashes + 1 {
+                    res.push('\\');
+                }
+                slashes = 0;
+            }
+            '#' => {
+                res.push('\\');
+                slashes = 0;
+            }
+            _ => slashes = 0,
+        };
+        res.push(c);
+    }
+    res
+}
+
+/// Extracts the current compilation's dependencies as paths relative to the
+/// current directory.
+fn relative_dependencies(world: &mut SystemWorld) -> io::Result<impl Iterator<Item = OsString>> {
+    let root = world.root().to_owned();
+    let current_dir = std::env::current_dir()?;
+    let relative_root = pathdiff::diff_paths(&root, &current_dir).unwrap_or_else(|| root.clone());
+    Ok(world.dependencies().map(move |dependency| {
+        dependency
+            .strip_prefix(&root)
+            .map_or_else(|_| dependency.clone(), |x| relative_root.join(x))
+            .into_os_string()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use typst::World;
+
+    use super::*;
+
+    #[test]
+    fn test_munge() {
+        assert_eq!(munge("out/main.pdf"), "out/main.pdf");
+        assert_eq!(munge("my file.typ"), "my\\ file.typ");
+        assert_eq!(munge("tab\tfile.typ"), "tab\\\tfile.typ");
+        assert_eq!(munge("$HOME.typ"), "$$HOME.typ");
+        assert_eq!(munge("#1.typ"), "\\#1.typ");
+        assert_eq!(munge("C:\\dir\\main.typ"), "C\\:\\dir\\main.typ");
+        // Backslashes are only doubled before a space, where they would
+        // escape it otherwise.
+        assert_eq!(munge("dir\\ main.typ"), "dir\\\\\\ main.typ");
+        assert_eq!(munge("dir\\\\ main.typ"), "dir\\\\\\\\\\ main.typ");
+    }
+
+    /// Write the JSON dependencies of a world in which the main file was read.
+    fn json(outputs: &[Output]) -> io::Result<serde_json::Value> {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("main.typ");
+        fs::write(&input, "= Hello").unwrap();
+        let mut world = crate::tests::renderer(&input).world;
+        world.source(world.main()).unwrap();
+
+        let dest = dir.path().join("deps.json");
+        write_deps_json(&mut world, &Output::Path(dest.clone()), Some(outputs))?;
+        Ok(serde_json::from_slice(&fs::read(dest).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_write_deps_json() {
+        let deps = json(&[Output::Path("main.pdf".into()), Output::Stdout]).unwrap();
+        let inputs = deps["inputs"].as_array().unwrap();
+        assert_eq!(inputs.len(), 1);
+        assert!(inputs[0].as_str().unwrap().ends_with("main.typ"));
+        assert_eq!(deps["outputs"], serde_json::json!(["main.pdf"]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_write_deps_json_with_non_utf8_path() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let path = PathBuf::from(OsString::from_vec(b"main-\xff.pdf".to_vec()));
+        let err = json(&[Output::Path(path)]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("is not valid utf-8"), "{err}");
+    }
+}
@@ -0,0 +1,210 @@
+use std::io::Write;
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::Files;
+use codespan_reporting::term;
+use ecow::{EcoString, eco_format};
+use serde::Serialize;
+use typst_library::WorldExt;
+use typst_library::diag::{Severity, SourceDiagnostic};
+use typst_syntax::{FileId, Lines, Span};
+
+use crate::SystemWorld;
+use crate::args::DiagnosticFormat;
+use crate::terminal;
+
+type CodespanResult<T> = Result<T, CodespanError>;
+type CodespanError = codespan_reporting::files::Error;
+
+/// Print diagnostic messages to the terminal.
+pub fn print_diagnostics(
+    world: &SystemWorld,
+    errors: &[SourceDiagnostic],
+    warnings: &[SourceDiagnostic],
+    diagnostic_format: DiagnosticFormat,
+) -> Result<(), codespan_reporting::files::Error> {
+    if diagnostic_format == DiagnosticFormat::Json {
+        return print_json(world, errors, warnings);
+    }
+
+    let mut config = term::Config {
+        tab_width: 2,
+        ..Default::default()
+    };
+    if diagnostic_format == DiagnosticFormat::Short {
+        config.display_style = term::DisplayStyle::Short;
+    }
+
+    for diagnostic in warnings.iter().chain(errors) {
+        let diag = match diagnostic.severity {
+            Severity::Error => Diagnostic::error(),
+            Severity::Warning => Diagnostic::warning(),
+        }
+        .with_message(diagnostic.message.clone())
+        .with_notes(
+            diagnostic
+                .hints
+                .iter()
+                .map(|e| (eco_format!("hint: {e}")).into())
+                .collect(),
+        )
+        .with_labels(label(world, diagnostic.span).into_iter().collect());
+
+        term::emit(&mut terminal::out(), &config, world, &diag)?;
+
+        // Stacktrace-like helper diagnostics.
+        for point in &diagnostic.trace {
+            let message = point.v.to_string();
+            let help = Diagnostic::help()
+                .with_message(message)
+                .with_labels(label(world, point.span).into_iter().collect());
+
+            term::emit(&mut terminal::out(), &config, world, &help)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Print diagnostic messages as JSON, one object per line.
+fn print_json(
+    world: &SystemWorld,
+    errors: &[SourceDiagnostic],
+    warnings: &[SourceDiagnostic],
+) -> CodespanResult<()> {
+    /// A diagnostic with its span resolved to a file location.
+    #[derive(Serialize)]
+    struct JsonDiagnostic<'a> {
+        severity: &'static str,
+        message: &'a str,
+        location: Option<Location>,
+        hints: &'a [EcoString],
+        trace: Vec<JsonTracepoint>,
+    }
+
+    /// A step in the trace of a diagnostic.
+    #[derive(Serialize)]
+    struct JsonTracepoint {
+        message: String,
+        location: Option<Location>,
+    }
+
+    let mut out = terminal::out();
+    for diagnostic in warnings.iter().chain(errors) {
+        let json = JsonDiagnostic {
+            severity: match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            message: &diagnostic.message,
+            location: locate(world, diagnostic.span)?,
+            hints: &diagnostic.hints,
+            trace: diagnostic
+                .trace
+                .iter()
+                .map(|point| {
+                    Ok(JsonTracepoint {
+                        message: point.v.to_string(),
+                        location: locate(world, point.span)?,
+                    })
+                })
+                .collect::<CodespanResult<_>>()?,
+        };
+
+        serde_json::to_writer(&mut out, &json).map_err(|err| CodespanError::Io(err.into()))?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// The file, line and column a span points to. Lines and columns are
+/// one-based, like in the human-readable output.
+#[derive(Serialize)]
+struct Location {
+    file: String,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+/// Resolve a span to its location in a file, if it has one.
+fn locate(world: &SystemWorld, span: Span) -> CodespanResult<Option<Location>> {
+    let Some((id, range)) = span.id().zip(world.range(span)) else {
+        return Ok(None);
+    };
+    let start = world.location(id, range.start)?;
+    let end = world.location(id, range.end)?;
+    Ok(Some(Location {
+        file: world.name(id)?,
+        line: start.line_number,
+        column: start.column_number,
+        end_line: end.line_number,
+        end_column: end.column_number,
+    }))
+}
+
+/// Create a label for a span.
+fn label(world: &SystemWorld, span: Span) -> Option<Label<FileId>> {
+    Some(Label::primary(span.id()?, world.range(span)?))
+}
+
+impl<'a> Files<'a> for SystemWorld {
+    type FileId = FileId;
+    type Name = String;
+    type Source = Lines<String>;
+
+    fn name(&'a self, id: FileId) -> CodespanResult<Self::Name> {
+        let vpath = id.vpath();
+        Ok(if let Some(package) = id.package() {
+            format!("{package}{}", vpath.as_rooted_path().display())
+        } else {
+            // Try to express the path relative to the working directory.
+            vpath
+                .resolve(self.root())
+                .and_then(|abs| pathdiff::diff_paths(abs, self.workdir()))
+                .as_deref()
+                .unwrap_or_else(|| vpath.as_rootless_path())
+                .to_string_lossy()
+                .into()
+        })
+    }
+
+    fn source(&'a self, id: FileId) -> CodespanResult<Self::Source> {
+        Ok(self.lookup(id))
+    }
+
+    fn line_index(&'a self, id: FileId, given: usize) -> CodespanResult<usize> {
+        let source = self.lookup(id);
+        source
+            .byte_to_line(given)
+            .ok_or_else(|| CodespanError::IndexTooLarge {
+                given,
+                max: source.len_bytes(),
+            })
+    }
+
+    fn line_range(&'a self, id: FileId, given: usize) -> CodespanResult<Range<usize>> {
+        let source = self.lookup(id);
+        source
+            .line_to_range(given)
+            .ok_or_else(|| CodespanError::LineTooLarge {
+                given,
+                max: source.len_lines(),
+            })
+    }
+
+    fn column_number(&'a self, id: FileId, _: usize, given: usize) -> CodespanResult<usize> {
+        let source = self.lookup(id);
+        source.byte_to_column(given).ok_or_else(|| {
+            let max = source.len_bytes();
+            if given <= max {
+                CodespanError::InvalidCharBoundary { given }
+            } else {
+                CodespanError::IndexTooLarge { given, max }
+            }
+        })
+    }
+}
@@ -0,0 +1,560 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ecow::{EcoString, eco_format};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use typst_library::diag::{HintedStrResult, HintedString, StrResult, bail};
+use typst_library::text::{FontBook, FontInfo, FontVariant};
+
+use crate::fonts::{FontSlot, Fonts};
+
+/// The first lines of every lockfile.
+const LOCKFILE_HEADER: &str = "\
+# Generated from the font manifest. Fonts without a path are embedded in the
+# binary. Delete this file to lock the fonts that are found now.
+";
+
+/// A list of the fonts that a project is compiled with, so that it looks the
+/// same on every machine.
+///
+/// The manifest is a TOML file like this:
+///
+/// ```toml
+/// # Whether the fonts embedded in the binary are used, `false` by default.
+/// embedded = true
+/// # Files and directories, relative to the manifest. Directories are searched
+/// # recursively. Files can be pinned to the hash of their contents.
+/// fonts = [
+///     "fonts",
+///     { path = "vendor/NotoSerifCJKsc-Regular.otf", hash = "2f6d…" },
+/// ]
+/// ```
+///
+/// System fonts are never used. The fonts that the manifest resolves to are
+/// recorded in a lockfile next to it, with the extension `.lock`. While the
+/// lockfile exists, exactly the same fonts must be found, in the same order
+/// and with the same contents. It also lists the hashes to pin files to.
+pub struct FontManifest {
+    /// The manifest file.
+    path: PathBuf,
+    /// Whether the fonts embedded in the binary are used.
+    embedded: bool,
+    /// The canonical paths of the listed files and directories, along with
+    /// the hashes that files are pinned to.
+    entries: Vec<(PathBuf, Option<String>)>,
+}
+
+/// The contents of a manifest file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    #[serde(default)]
+    embedded: bool,
+    #[serde(default)]
+    fonts: Vec<ManifestEntry>,
+}
+
+/// A font file or directory in a manifest file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ManifestEntry {
+    Path(PathBuf),
+    Pinned { path: PathBuf, hash: String },
+}
+
+/// The contents of a lockfile.
+#[derive(Serialize, Deserialize)]
+struct Lockfile {
+    #[serde(default, rename = "font")]
+    fonts: Vec<LockedFont>,
+}
+
+/// A font as it was resolved, in the order of the font book.
+#[derive(Serialize, Deserialize)]
+struct LockedFont {
+    /// The file, relative to the manifest and with forward slashes. Fonts
+    /// embedded in the binary have none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    /// The index of the font in its file.
+    index: u32,
+    family: String,
+    #[serde(flatten)]
+    variant: FontVariant,
+    /// The hash of the whole file.
+    hash: String,
+}
+
+impl FontManifest {
+    /// Read a manifest and check that everything it lists exists.
+    pub fn load(path: &Path) -> StrResult<Self> {
+        let text = fs::read_to_string(path).map_err(|err| {
+            eco_format!("failed to read font manifest {} ({err})", path.display())
+        })?;
+        let file: ManifestFile = toml::from_str(&text).map_err(|err| {
+            eco_format!("failed to parse font manifest {} ({err})", path.display())
+        })?;
+
+        // The manifest could be read, so it can be canonicalized, too.
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let entries = file
+            .fonts
+            .into_iter()
+            .map(|entry| {
+                let (relative, hash) = match entry {
+                    ManifestEntry::Path(path) => (path, None),
+                    ManifestEntry::Pinned { path, hash } => (path, Some(hash)),
+                };
+                let path = dir.join(&relative).canonicalize().map_err(|err| {
+                    eco_format!("failed to find font path {} ({err})", relative.display())
+                })?;
+                if hash.is_some() && !path.is_file() {
+                    bail!(
+                        "only files can be pinned to a hash ({})",
+                        relative.display()
+                    );
+                }
+                Ok((path, hash))
+            })
+            .collect::<StrResult<_>>()?;
+
+        Ok(Self {
+            path,
+            embedded: file.embedded,
+            entries,
+        })
+    }
+
+    /// The manifest and the files and directories it lists.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let entries = self.entries.iter().map(|(path, _)| path.clone());
+        std::iter::once(self.path.clone()).chain(entries).collect()
+    }
+
+    /// Find the listed fonts and check them against the lockfile, or write
+    /// one if there is none yet.
+    pub fn resolve(&self) -> HintedStrResult<Fonts> {
+        // Files are hashed at most once, even if they contain many fonts.
+        let mut hashes = FxHashMap::default();
+        for (path, pinned) in &self.entries {
+            let Some(pinned) = pinned else { continue };
+            let hash = hash_file(path, &mut hashes)?;
+            if hash != *pinned {
+                let mut error = HintedString::from(eco_format!(
+                    "font file {} does not match its hash in the manifest",
+                    path.display()
+                ));
+                error.hint(eco_format!("the hash of the file is now {hash}"));
+                return Err(error);
+            }
+        }
+
+        let fonts = self.search();
+        for (path, _) in &self.entries {
+            if !fonts.fonts.iter().any(|slot| lists(path, slot)) {
+                bail!("no fonts found at {}", path.display());
+            }
+        }
+
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        let found = fonts
+            .fonts
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| {
+                let info = fonts.book.info(i).expect("every font has an info");
+                lock_font(slot, info, dir, &mut hashes)
+            })
+            .collect::<StrResult<Vec<_>>>()?;
+
+        let path = self.path.with_extension("lock");
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                let locked: Lockfile = toml::from_str(&text).map_err(|err| {
+                    eco_format!("failed to parse font lockfile {} ({err})", path.display())
+                })?;
+                check(&locked.fonts, &found).map_err(|message| {
+                    let mut error = HintedString::from(message);
+                    error.hint(eco_format!(
+                        "delete {} to lock the fonts that are found now",
+                        path.display()
+                    ));
+                    error
+                })?;
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let lockfile = Lockfile { fonts: found };
+                let text = toml::to_string(&lockfile)
+                    .map_err(|err| eco_format!("failed to serialize font lockfile ({err})"))?;
+                fs::write(&path, format!("{LOCKFILE_HEADER}\n{text}")).map_err(|err| {
+                    eco_format!("failed to write font lockfile {} ({err})", path.display())
+                })?;
+                tracing::info!(path = %path.display(), fonts = lockfile.fonts.len(), "locked fonts");
+            }
+            Err(err) => {
+                bail!("failed to read font lockfile {} ({err})", path.display());
+            }
+        }
+
+        Ok(fonts)
+    }
+
+    /// Search the listed directories and load the listed files. The fonts
+    /// are ordered like the entries that list them and then by path, so that
+    /// the order does not depend on the file system. Embedded fonts come last.
+    fn search(&self) -> Fonts {
+        let (dirs, files): (Vec<_>, Vec<_>) = self
+            .entries
+            .iter()
+            .map(|(path, _)| path)
+            .partition(|path| path.is_dir());
+
+        let mut searcher = typst_kit::fonts::Fonts::searcher();
+        searcher.include_system_fonts(false);
+        searcher.include_embedded_fonts(self.embedded);
+        let fonts = Fonts::from(searcher.search_with(dirs));
+
+        let mut found: Vec<(FontSlot, FontInfo)> = fonts
+            .fonts
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((slot, fonts.book.info(i)?.clone())))
+            .chain(files.into_iter().flat_map(|path| load_file(path)))
+            .filter(|(slot, _)| self.rank(slot).is_some())
+            .collect();
+        found.sort_by_cached_key(|(slot, _)| {
+            (
+                self.rank(slot),
+                slot.path().map(Path::to_path_buf),
+                slot.index(),
+            )
+        });
+        found.dedup_by(|(a, _), (b, _)| {
+            a.path().is_some() && a.path() == b.path() && a.index() == b.index()
+        });
+
+        let (fonts, infos): (Vec<_>, Vec<_>) = found.into_iter().unzip();
+        Fonts {
+            book: FontBook::from_infos(infos),
+            fonts,
+        }
+    }
+
+    /// The position of the first entry that lists the font, or `None` if no
+    /// entry does. Embedded fonts are ranked after all entries.
+    fn rank(&self, slot: &FontSlot) -> Option<usize> {
+        if slot.path().is_none() {
+            return Some(self.entries.len());
+        }
+        self.entries
+            .iter()
+            .position(|(entry, _)| lists(entry, slot))
+    }
+}
+
+/// Read the fonts in a file, which are loaded again on first access. A file
+/// that cannot be read has no fonts.
+fn load_file(path: &Path) -> Vec<(FontSlot, FontInfo)> {
+    let Ok(data) = fs::read(path) else {
+        return vec![];
+    };
+    let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+    (0..count)
+        .filter_map(|index| {
+            let info = FontInfo::new(&data, index)?;
+            Some((FontSlot::new(path.to_path_buf(), index), info))
+        })
+        .collect()
+}
+
+/// Whether a file or directory of the manifest contains a font.
+fn lists(entry: &Path, slot: &FontSlot) -> bool {
+    match slot.path() {
+        Some(path) if entry.is_dir() => path.starts_with(entry),
+        Some(path) => path == entry,
+        None => false,
+    }
+}
+
+/// Describe a font for the lockfile.
+fn lock_font(
+    slot: &FontSlot,
+    info: &FontInfo,
+    dir: &Path,
+    hashes: &mut FxHashMap<PathBuf, String>,
+) -> StrResult<LockedFont> {
+    let (path, hash) = match slot.path() {
+        Some(path) => {
+            let relative = pathdiff::diff_paths(path, dir).unwrap_or_else(|| path.to_path_buf());
+            let relative = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            (Some(relative), hash_file(path, hashes)?)
+        }
+        None => {
+            let font = slot.get().expect("embedded fonts are always loaded");
+            (None, hash_bytes(font.data()))
+        }
+    };
+
+    Ok(LockedFont {
+        path,
+        index: slot.index(),
+        family: info.family.clone(),
+        variant: info.variant,
+        hash,
+    })
+}
+
+/// Check that the fonts that were found are the locked ones.
+fn check(locked: &[LockedFont], found: &[LockedFont]) -> Result<(), EcoString> {
+    for font in locked {
+        let Some(other) = found.iter().find(|other| font.is_same_face(other)) else {
+            return Err(eco_format!("locked font {} is missing", font.describe()));
+        };
+        if font.hash != other.hash || font.family != other.family || font.variant != other.variant {
+            return Err(eco_format!(
+                "font {} changed since it was locked",
+                font.describe()
+            ));
+        }
+    }
+
+    if let Some(font) = found
+        .iter()
+        .find(|font| !locked.iter().any(|other| font.is_same_face(other)))
+    {
+        return Err(eco_format!("font {} is not locked", font.describe()));
+    }
+
+    if !locked.iter().zip(found).all(|(a, b)| a.is_same_face(b)) {
+        return Err("fonts are found in a different order than they were locked".into());
+    }
+
+    Ok(())
+}
+
+impl LockedFont {
+    /// Whether both are the same font of the same file, regardless of the
+    /// contents of the file. Embedded fonts are told apart by their names.
+    fn is_same_face(&self, other: &Self) -> bool {
+        if self.path.is_some() {
+            self.path == other.path && self.index == other.index
+        } else {
+            other.path.is_none() && self.family == other.family && self.variant == other.variant
+        }
+    }
+
+    /// The family and file of the font, for error messages.
+    fn describe(&self) -> EcoString {
+        match &self.path {
+            Some(path) if self.index > 0 => {
+                eco_format!("{} ({path}, index {})", self.family, self.index)
+            }
+            Some(path) => eco_format!("{} ({path})", self.family),
+            None => eco_format!("{} (embedded)", self.family),
+        }
+    }
+}
+
+/// Hash a font file, unless it was hashed before.
+fn hash_file(path: &Path, hashes: &mut FxHashMap<PathBuf, String>) -> StrResult<String> {
+    if let Some(hash) = hashes.get(path) {
+        return Ok(hash.clone());
+    }
+    let data = fs::read(path)
+        .map_err(|err| eco_format!("failed to read font file {} ({err})", path.display()))?;
+    let hash = hash_bytes(&data);
+    hashes.insert(path.to_path_buf(), hash.clone());
+    Ok(hash)
+}
+
+/// Hash the contents of a font file with SHA-256, as lowercase hex.
+fn hash_bytes(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use tempfile::TempDir;
+    use typst_library::diag::{HintedStrResult, HintedString};
+    use typst_library::foundations::Bytes;
+
+    use super::{FontManifest, hash_bytes};
+    use crate::fonts::Fonts;
+
+    /// A directory with a font manifest, whose fonts are copied from the ones
+    /// embedded in the binary.
+    struct Project(TempDir);
+
+    impl Project {
+        fn new(manifest: &str) -> Self {
+            let project = Self(TempDir::new().unwrap());
+            project.manifest(manifest);
+            project
+        }
+
+        fn manifest(&self, text: &str) {
+            fs::write(self.path("fonts.toml"), text).unwrap();
+        }
+
+        /// Copy an embedded font of a family to a file of the project.
+        fn font(&self, path: &str, family: &str) {
+            let path = self.path(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, embedded(family)).unwrap();
+        }
+
+        fn path(&self, path: &str) -> PathBuf {
+            self.0.path().join(path)
+        }
+
+        fn resolve(&self) -> HintedStrResult<Fonts> {
+            FontManifest::load(&self.path("fonts.toml"))?.resolve()
+        }
+
+        /// Resolve the manifest and return the families of the fonts.
+        fn families(&self) -> Vec<String> {
+            let fonts = self
+                .resolve()
+                .unwrap_or_else(|err| panic!("{}", err.message()));
+            (0..fonts.fonts.len())
+                .map(|i| fonts.book.info(i).unwrap().family.clone())
+                .collect()
+        }
+
+        /// Resolve the manifest, which should fail.
+        fn error(&self) -> HintedString {
+            self.resolve()
+                .err()
+                .expect("the manifest should not resolve")
+        }
+    }
+
+    /// The data of the first embedded font of a family.
+    fn embedded(family: &str) -> Bytes {
+        let mut searcher = typst_kit::fonts::Fonts::searcher();
+        searcher.include_system_fonts(false);
+        let fonts = searcher.search_with::<_, &str>([]);
+        let index = (0..fonts.fonts.len())
+            .find(|&i| fonts.book.info(i).unwrap().family == family)
+            .unwrap();
+        fonts.fonts[index].get().unwrap().data().clone()
+    }
+
+    #[test]
+    fn test_resolve_writes_lockfile() {
+        let project = Project::new(r#"fonts = ["fonts"]"#);
+        project.font("fonts/b.otf", "DejaVu Sans Mono");
+        project.font("fonts/a.otf", "Libertinus Serif");
+
+        assert_eq!(project.families(), ["Libertinus Serif", "DejaVu Sans Mono"]);
+        let lock = fs::read_to_string(project.path("fonts.lock")).unwrap();
+        let hash = hash_bytes(&embedded("Libertinus Serif"));
+        assert_eq!(hash.len(), 64);
+        assert!(lock.contains(r#"path = "fonts/a.otf""#));
+        assert!(lock.contains(&format!(r#"hash = "{hash}""#)));
+
+        // The lockfile is only checked from now on.
+        assert_eq!(project.families(), ["Libertinus Serif", "DejaVu Sans Mono"]);
+        assert_eq!(
+            fs::read_to_string(project.path("fonts.lock")).unwrap(),
+            lock
+        );
+    }
+
+    #[test]
+    fn test_locked_font_is_missing() {
+        let project = Project::new(r#"fonts = ["fonts"]"#);
+        project.font("fonts/a.otf", "Libertinus Serif");
+        project.font("fonts/b.otf", "DejaVu Sans Mono");
+        project.families();
+
+        fs::remove_file(project.path("fonts/b.otf")).unwrap();
+        let err = project.error();
+        assert_eq!(
+            err.message(),
+            "locked font DejaVu Sans Mono (fonts/b.otf) is missing"
+        );
+        assert!(err.hints()[0].starts_with("delete "));
+    }
+
+    #[test]
+    fn test_locked_font_changed() {
+        let project = Project::new(r#"fonts = ["fonts"]"#);
+        project.font("fonts/a.otf", "Libertinus Serif");
+        project.families();
+
+        project.font("fonts/a.otf", "DejaVu Sans Mono");
+        assert_eq!(
+            project.error().message(),
+            "font Libertinus Serif (fonts/a.otf) changed since it was locked"
+        );
+    }
+
+    #[test]
+    fn test_font_is_not_locked() {
+        let project = Project::new(r#"fonts = ["fonts"]"#);
+        project.font("fonts/a.otf", "Libertinus Serif");
+        project.families();
+
+        project.font("fonts/b.otf", "DejaVu Sans Mono");
+        assert_eq!(
+            project.error().message(),
+            "font DejaVu Sans Mono (fonts/b.otf) is not locked"
+        );
+    }
+
+    #[test]
+    fn test_fonts_are_reordered() {
+        let project = Project::new(r#"fonts = ["a.otf", "b.otf"]"#);
+        project.font("a.otf", "Libertinus Serif");
+        project.font("b.otf", "DejaVu Sans Mono");
+        assert_eq!(project.families(), ["Libertinus Serif", "DejaVu Sans Mono"]);
+
+        project.manifest(r#"fonts = ["b.otf", "a.otf"]"#);
+        assert_eq!(
+            project.error().message(),
+            "fonts are found in a different order than they were locked"
+        );
+    }
+
+    #[test]
+    fn test_pinned_hash_mismatch() {
+        let project = Project::new(r#"fonts = [{ path = "a.otf", hash = "00" }]"#);
+        project.font("a.otf", "Libertinus Serif");
+
+        let hash = hash_bytes(&embedded("Libertinus Serif"));
+        let err = project.error();
+        assert!(
+            err.message()
+                .ends_with("does not match its hash in the manifest")
+        );
+        assert_eq!(err.hints(), [format!("the hash of the file is now {hash}")]);
+        assert!(!project.path("fonts.lock").exists());
+
+        project.manifest(&format!(
+            r#"fonts = [{{ path = "a.otf", hash = "{hash}" }}]"#
+        ));
+        assert_eq!(project.families(), ["Libertinus Serif"]);
+    }
+
+    #[test]
+    fn test_pinned_file_is_loaded_alone() {
+        let project = Project::new(r#"fonts = ["a.otf"]"#);
+        project.font("a.otf", "Libertinus Serif");
+        project.font("b.otf", "DejaVu Sans Mono");
+        project.font("sub/c.otf", "DejaVu Sans Mono");
+
+        assert_eq!(project.families(), ["Libertinus Serif"]);
+    }
+}
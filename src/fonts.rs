@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use ecow::eco_format;
+use ttf_parser::{Face, name_id};
+use typst_library::diag::{HintedStrResult, HintedString};
+use typst_library::foundations::Bytes;
+use typst_library::text::{Font, FontBook, FontInfo, FontVariant};
+use typst_timing::TimingScope;
+
+use crate::args::{FontArgs, FontsCommand};
+use crate::font_manifest::FontManifest;
+
+/// Execute a font listing command.
+pub fn fonts(command: &FontsCommand) -> HintedStrResult<()> {
+    let (fonts, _) = search(&command.font)?;
+
+    // The characters to look for, without duplicates.
+    let mut chars: Vec<char> = command
+        .covers
+        .iter()
+        .flat_map(|text| text.chars())
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    chars.sort_unstable();
+    chars.dedup();
+    let covers = |info: &FontInfo| chars.iter().all(|&c| info.coverage.contains(c as u32));
+
+    let mut found = false;
+    for (family, _) in fonts.book.families() {
+        let indices: Vec<usize> = fonts
+            .book
+            .select_family(&family.to_lowercase())
+            .filter(|&index| fonts.book.info(index).is_some_and(covers))
+            .collect();
+        if indices.is_empty() {
+            continue;
+        }
+
+        found = true;
+        println!("{family}");
+        if command.variants {
+            for index in indices {
+                let (Some(info), Some(slot)) = (fonts.book.info(index), fonts.fonts.get(index))
+                else {
+                    continue;
+                };
+                print_variant(info, slot);
+            }
+        }
+    }
+
+    if let Some(text) = &command.covers
+        && !found
+    {
+        return Err(uncovered(&fonts, &chars, text));
+    }
+
+    Ok(())
+}
+
+/// Find the fonts that the arguments ask for. Returns them along with the
+/// paths they were searched at, which should be watched for changes.
+pub fn search(args: &FontArgs) -> HintedStrResult<(Fonts, Vec<PathBuf>)> {
+    if let Some(path) = &args.font_manifest {
+        let manifest = FontManifest::load(path)?;
+        return Ok((manifest.resolve()?, manifest.paths()));
+    }
+
+    let mut fonts = typst_kit::fonts::Fonts::searcher();
+    fonts.include_system_fonts(!args.ignore_system_fonts);
+    fonts.include_embedded_fonts(!args.ignore_embedded_fonts);
+    let fonts = fonts.search_with(&args.font_paths).into();
+    Ok((fonts, args.font_paths.clone()))
+}
+
+/// The fonts that were found.
+pub struct Fonts {
+    /// Metadata about the fonts.
+    pub book: FontBook,
+    /// Slots that the fonts are loaded into, in the order of the book.
+    pub fonts: Vec<FontSlot>,
+}
+
+impl From<typst_kit::fonts::Fonts> for Fonts {
+    fn from(fonts: typst_kit::fonts::Fonts) -> Self {
+        Self {
+            book: fonts.book,
+            fonts: fonts.fonts.into_iter().map(FontSlot::from).collect(),
+        }
+    }
+}
+
+/// Where a font can be found, along with the font once it is loaded. Unlike
+/// the slots of `typst_kit`, these can be created for single files.
+pub struct FontSlot {
+    /// The file of the font, or `None` if it is embedded in the binary.
+    path: Option<PathBuf>,
+    /// The index of the font in its collection.
+    index: u32,
+    /// The lazily loaded font.
+    font: OnceLock<Option<Font>>,
+}
+
+impl FontSlot {
+    /// A slot for a font in a file, which is loaded on first access.
+    pub fn new(path: PathBuf, index: u32) -> Self {
+        Self {
+            path: Some(path),
+            index,
+            font: OnceLock::new(),
+        }
+    }
+
+    /// The file of the font, or `None` if it is embedded in the binary.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The index of the font in its collection.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Get the font, loading it on first access.
+    pub fn get(&self) -> Option<Font> {
+        self.font
+            .get_or_init(|| {
+                let _scope = TimingScope::new("load font");
+                let data = fs::read(self.path.as_ref()?).ok()?;
+                Font::new(Bytes::new(data), self.index)
+            })
+            .clone()
+    }
+}
+
+impl From<typst_kit::fonts::FontSlot> for FontSlot {
+    fn from(slot: typst_kit::fonts::FontSlot) -> Self {
+        match slot.path() {
+            Some(path) => Self::new(path.to_path_buf(), slot.index()),
+            // Embedded fonts are always loaded already.
+            None => Self {
+                path: None,
+                index: slot.index(),
+                font: OnceLock::from(slot.get()),
+            },
+        }
+    }
+}
+
+/// Print the style, origin and names of a font.
+fn print_variant(info: &FontInfo, slot: &FontSlot) {
+    let FontVariant {
+        style,
+        weight,
+        stretch,
+    } = info.variant;
+    let stretch = stretch.to_ratio();
+    println!("- Style: {style:?}, Weight: {weight:?}, Stretch: {stretch:?}");
+
+    match slot.path() {
+        Some(path) if slot.index() > 0 => {
+            println!("  Path: {} (index {})", path.display(), slot.index())
+        }
+        Some(path) => println!("  Path: {}", path.display()),
+        None => println!("  Embedded in the binary"),
+    }
+
+    // Loading the font can fail if its file changed since the search.
+    if let Some(font) = slot.get() {
+        let names = FontNames::new(font.ttf());
+        println!("  Full name: {}", names.full_name.as_deref().unwrap_or("?"));
+        println!(
+            "  PostScript name: {}",
+            names.postscript.as_deref().unwrap_or("?")
+        );
+    }
+}
+
+/// Explain why no font covers a text.
+fn uncovered(fonts: &Fonts, chars: &[char], text: &str) -> HintedString {
+    let missing: Vec<String> = chars
+        .iter()
+        .filter(|&&c| {
+            fonts
+                .book
+                .families()
+                .flat_map(|(_, infos)| infos)
+                .all(|info| !info.coverage.contains(c as u32))
+        })
+        .map(|c| format!("{c:?} (U+{:04X})", *c as u32))
+        .collect();
+
+    if missing.is_empty() {
+        let mut message = HintedString::from(eco_format!("no single font covers {text:?}"));
+        message.hint("every character is covered by some font, which Typst falls back to");
+        message
+    } else {
+        let mut message = HintedString::from(eco_format!("no font covers {}", missing.join(", ")));
+        message.hint("add a font that covers them with `--font-path`");
+        message
+    }
+}
+
+/// The names a font gives itself, as far as they are in Unicode.
+pub struct FontNames {
+    /// The family, preferring the typographic one, e.g. "Noto Sans CJK SC".
+    pub family: Option<String>,
+    /// The style within the family, e.g. "Bold Italic".
+    pub subfamily: Option<String>,
+    /// The full name, e.g. "Noto Sans CJK SC Bold".
+    pub full_name: Option<String>,
+    /// The PostScript name, e.g. "NotoSansCJKsc-Bold".
+    pub postscript: Option<String>,
+}
+
+impl FontNames {
+    /// Read the names from a font's name table.
+    pub fn new(face: &Face) -> Self {
+        let lookup = |ids: &[u16]| {
+            ids.iter().find_map(|&id| {
+                face.names()
+                    .into_iter()
+                    .filter(|name| name.name_id == id)
+                    .find_map(|name| name.to_string())
+            })
+        };
+
+        Self {
+            family: lookup(&[name_id::TYPOGRAPHIC_FAMILY, name_id::FAMILY]),
+            subfamily: lookup(&[name_id::TYPOGRAPHIC_SUBFAMILY, name_id::SUBFAMILY]),
+            full_name: lookup(&[name_id::FULL_NAME]),
+            postscript: lookup(&[name_id::POST_SCRIPT_NAME]),
+        }
+    }
+}
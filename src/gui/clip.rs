@@ -0,0 +1,220 @@
+use eframe::egui::{Color32, Mesh, Pos2, Rect};
+use eframe::epaint::Vertex;
+use lyon_tessellation::path::PathEvent;
+use lyon_tessellation::path::iterator::PathIterator as _;
+use tiny_skia as sk;
+use typst_library::visualize::FillRule;
+
+use super::shapes::{TOLERANCE, tessellate, to_lyon_path};
+
+/// An area on the screen that drawing is clipped to, in addition to the ui's
+/// clip rect. It is made of convex polygons that do not overlap, so that
+/// meshes can be clipped to each of them separately.
+#[derive(Clone)]
+pub(super) struct Clip(Vec<Convex>);
+
+/// A convex polygon on the screen, with its corners ordered such that the
+/// polygon turns the same way as the screen's axes.
+#[derive(Clone)]
+struct Convex {
+    points: Vec<Pos2>,
+    bounds: Rect,
+}
+
+/// The result of clipping a group with a path.
+pub(super) enum GroupClip {
+    /// The path is an axis-aligned rectangle, so the ui's clip rect alone
+    /// clips the group exactly.
+    Rect(Rect),
+    /// The path is of another shape, so the group's meshes are clipped to it
+    /// in addition to clipping to its bounds with the ui's clip rect.
+    Path(Rect, Clip),
+}
+
+impl GroupClip {
+    /// Classify a clip path given in screen coordinates.
+    pub fn new(path: &sk::Path) -> Self {
+        let mut points = vec![];
+        let mut subpaths = 0;
+        for event in to_lyon_path(path).iter().flattened(TOLERANCE) {
+            match event {
+                PathEvent::Begin { at } => {
+                    points.push(Pos2::new(at.x, at.y));
+                    subpaths += 1;
+                }
+                PathEvent::Line { to, .. } => points.push(Pos2::new(to.x, to.y)),
+                _ => {}
+            }
+        }
+        points.dedup();
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+
+        let bounds = Rect::from_points(&points);
+        let on_corner = |p: &Pos2| {
+            let near = |a: f32, b: f32| (a - b).abs() < 1e-3;
+            (near(p.x, bounds.min.x) || near(p.x, bounds.max.x))
+                && (near(p.y, bounds.min.y) || near(p.y, bounds.max.y))
+        };
+
+        if points.iter().all(on_corner) {
+            return Self::Rect(bounds);
+        }
+
+        // Most other clips, like rounded or rotated rectangles, are convex.
+        // The others are split into triangles.
+        if subpaths == 1
+            && let Some(convex) = Convex::new(points)
+        {
+            return Self::Path(bounds, Clip(vec![convex]));
+        }
+        match tessellate(path, FillRule::NonZero, Color32::WHITE, TOLERANCE) {
+            Some(mesh) => {
+                let triangles = mesh.indices.chunks_exact(3).filter_map(|triangle| {
+                    Convex::new(
+                        triangle
+                            .iter()
+                            .map(|&index| mesh.vertices[index as usize].pos)
+                            .collect(),
+                    )
+                });
+                Self::Path(bounds, Clip(triangles.collect()))
+            }
+            None => Self::Rect(bounds),
+        }
+    }
+
+    /// The bounding box of the clip path.
+    pub fn bounds(&self) -> Rect {
+        match self {
+            Self::Rect(bounds) | Self::Path(bounds, _) => *bounds,
+        }
+    }
+}
+
+impl Convex {
+    /// Creates a convex polygon from its corners if they form one.
+    fn new(mut points: Vec<Pos2>) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+
+        // The signs of the turns at the corners tell whether the polygon is
+        // convex and in which direction it winds.
+        let n = points.len();
+        let mut sign = 0.0;
+        for i in 0..n {
+            let (a, b, c) = (points[i], points[(i + 1) % n], points[(i + 2) % n]);
+            let turn = (b - a).x * (c - b).y - (b - a).y * (c - b).x;
+            if turn.abs() < 1e-6 {
+                continue;
+            }
+            if sign == 0.0 {
+                sign = turn.signum();
+            } else if turn.signum() != sign {
+                return None;
+            }
+        }
+
+        if sign == 0.0 {
+            return None;
+        } else if sign < 0.0 {
+            points.reverse();
+        }
+
+        let bounds = Rect::from_points(&points);
+        Some(Self { points, bounds })
+    }
+
+    /// Whether a point is on the inner side of the edge from `a` to `b`.
+    fn inside(a: Pos2, b: Pos2, p: Pos2) -> bool {
+        (b - a).x * (p - a).y - (b - a).y * (p - a).x >= 0.0
+    }
+
+    /// Cut away the parts of a polygon outside of the edges.
+    fn clip_polygon(&self, mut polygon: Vec<Vertex>) -> Vec<Vertex> {
+        let n = self.points.len();
+        for i in 0..n {
+            let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+            let input = std::mem::take(&mut polygon);
+            for (j, &current) in input.iter().enumerate() {
+                let previous = input[(j + input.len() - 1) % input.len()];
+                let (now_in, was_in) = (
+                    Self::inside(a, b, current.pos),
+                    Self::inside(a, b, previous.pos),
+                );
+                if now_in != was_in {
+                    polygon.push(intersect(a, b, previous, current));
+                }
+                if now_in {
+                    polygon.push(current);
+                }
+            }
+            if polygon.is_empty() {
+                break;
+            }
+        }
+        polygon
+    }
+}
+
+impl Clip {
+    /// Split a convex polygon into the parts within the clip's polygons.
+    fn clip_polygon(&self, polygon: &[Vertex]) -> impl Iterator<Item = Vec<Vertex>> {
+        let bounds = Rect::from_points(&polygon.iter().map(|v| v.pos).collect::<Vec<_>>());
+        self.0
+            .iter()
+            .filter(move |convex| convex.bounds.intersects(bounds))
+            .map(|convex| convex.clip_polygon(polygon.to_vec()))
+            .filter(|part| part.len() >= 3)
+    }
+}
+
+/// Clip a mesh to all of the clips, interpolating the colors and texture
+/// coordinates of the new vertices.
+pub(super) fn clip_mesh(mesh: Mesh, clips: &[Clip]) -> Mesh {
+    if clips.is_empty() {
+        return mesh;
+    }
+
+    let mut clipped = Mesh::with_texture(mesh.texture_id);
+    for triangle in mesh.indices.chunks_exact(3) {
+        let mut polygons = vec![
+            triangle
+                .iter()
+                .map(|&index| mesh.vertices[index as usize])
+                .collect::<Vec<_>>(),
+        ];
+        for clip in clips {
+            polygons = polygons
+                .iter()
+                .flat_map(|polygon| clip.clip_polygon(polygon))
+                .collect();
+        }
+
+        // A clipped triangle is still convex and can be split into a fan.
+        for polygon in polygons {
+            let start = clipped.vertices.len() as u32;
+            for k in 1..polygon.len() as u32 - 1 {
+                clipped.add_triangle(start, start + k, start + k + 1);
+            }
+            clipped.vertices.extend(polygon);
+        }
+    }
+    clipped
+}
+
+/// The point at which the edge from `from` to `to` crosses the line through
+/// `a` and `b`.
+fn intersect(a: Pos2, b: Pos2, from: Vertex, to: Vertex) -> Vertex {
+    let edge = b - a;
+    let cross = |p: Pos2| edge.x * (p - a).y - edge.y * (p - a).x;
+    let (d0, d1) = (cross(from.pos), cross(to.pos));
+    let t = d0 / (d0 - d1);
+    Vertex {
+        pos: from.pos.lerp(to.pos, t),
+        uv: from.uv.lerp(to.uv, t),
+        color: from.color.lerp_to_gamma(to.color, t),
+    }
+}
@@ -0,0 +1,165 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use eframe::egui::Context;
+use typst_library::World as _;
+use typst_library::diag::Warned;
+use typst_library::layout::PagedDocument;
+use typst_syntax::Source;
+
+use super::diagnostics::Diagnostic;
+use super::pages::PageNumbers;
+use super::sync::SpanIndex;
+use crate::Renderer;
+use crate::args::Pages;
+
+/// How long an edited source must stay unchanged before it is compiled.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// What the worker should compile.
+pub(super) enum Job {
+    /// The file at the path, which becomes the main file.
+    Path(PathBuf),
+    /// Source text that was edited in the ui.
+    Source(String),
+}
+
+/// The outcome of compiling a job.
+pub(super) struct Compiled {
+    /// The selected pages of the document, or `None` if compilation failed.
+    pub document: Option<PagedDocument>,
+    /// The numbers of the selected pages in the whole document.
+    pub numbers: PageNumbers,
+    /// The boxes of the document's items by the spans that produced them.
+    pub spans: SpanIndex,
+    /// The errors and warnings of the compilation.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The main file as it was compiled, which the spans in the document
+    /// point into.
+    pub source: Option<Source>,
+}
+
+/// Compiles documents on a background thread, so that a slow document doesn't
+/// freeze the ui.
+///
+/// Jobs that are submitted while the worker is busy or while an edit is still
+/// being debounced are coalesced, such that only the most recent one is
+/// compiled.
+pub(super) struct Compiler {
+    /// Sends jobs to the worker, tagged with their number.
+    jobs: Sender<(u64, Job)>,
+    /// Receives the results from the worker, tagged with the number of the
+    /// job they belong to.
+    results: Receiver<(u64, Compiled)>,
+    /// The number of the most recently submitted job.
+    submitted: u64,
+    /// The number of the job whose result was received last.
+    finished: u64,
+}
+
+impl Compiler {
+    /// Start a worker that compiles with the renderer, keeping only the
+    /// selected pages, and repaints the ui whenever a result is ready.
+    pub fn spawn(renderer: Renderer, pages: Option<Vec<Pages>>, ctx: Context) -> Self {
+        let (jobs, job_receiver) = mpsc::channel();
+        let (result_sender, results) = mpsc::channel();
+        thread::Builder::new()
+            .name("compiler".into())
+            .spawn(move || work(renderer, pages, job_receiver, result_sender, ctx))
+            .expect("failed to spawn the compiler thread");
+
+        Self {
+            jobs,
+            results,
+            submitted: 0,
+            finished: 0,
+        }
+    }
+
+    /// Queue a job, superseding all jobs that weren't started yet.
+    pub fn submit(&mut self, job: Job) {
+        self.submitted += 1;
+        self.jobs.send((self.submitted, job)).ok();
+    }
+
+    /// Take the result of the most recent job that finished since the last
+    /// call, if any.
+    pub fn poll(&mut self) -> Option<Compiled> {
+        let (number, compiled) = self.results.try_iter().last()?;
+        self.finished = number;
+        Some(compiled)
+    }
+
+    /// Whether a submitted job has not finished yet.
+    pub fn is_busy(&self) -> bool {
+        self.finished < self.submitted
+    }
+}
+
+/// Compile the jobs as they come in until the ui goes away.
+fn work(
+    mut renderer: Renderer,
+    pages: Option<Vec<Pages>>,
+    jobs: Receiver<(u64, Job)>,
+    results: Sender<(u64, Compiled)>,
+    ctx: Context,
+) {
+    let ranges = pages.as_deref().map(crate::page_ranges);
+    while let Ok(mut job) = jobs.recv() {
+        // Files are compiled right away, while edits are only compiled once
+        // typing pauses. Any job that comes in meanwhile replaces the current
+        // one.
+        loop {
+            if let Ok(newer) = jobs.try_recv() {
+                job = newer;
+                continue;
+            }
+            if matches!(job.1, Job::Path(_)) {
+                break;
+            }
+            match jobs.recv_timeout(DEBOUNCE) {
+                Ok(newer) => job = newer,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        // Files whose contents did not change keep their fingerprint and are
+        // not parsed again.
+        renderer.world.reset();
+        let (number, job) = job;
+        let Warned { output, warnings } = match job {
+            Job::Path(path) => renderer.render_from_path(&path, pages.as_deref()),
+            Job::Source(source) => renderer.render_from_string(source, pages.as_deref()),
+        };
+        comemo::evict(10);
+
+        let (document, numbers, spans, errors) = match output {
+            Ok(mut document) => {
+                let numbers = PageNumbers::select(&mut document, ranges.as_ref());
+                let spans = SpanIndex::new(&document);
+                (Some(document), numbers, spans, Default::default())
+            }
+            Err(errors) => (None, PageNumbers::default(), SpanIndex::default(), errors),
+        };
+        let diagnostics = errors
+            .iter()
+            .chain(&warnings)
+            .map(|diagnostic| Diagnostic::new(&renderer.world, diagnostic))
+            .collect();
+        let source = renderer.world.source(renderer.world.main()).ok();
+        let compiled = Compiled {
+            document,
+            numbers,
+            spans,
+            diagnostics,
+            source,
+        };
+        if results.send((number, compiled)).is_err() {
+            return;
+        }
+        ctx.request_repaint();
+    }
+}
@@ -0,0 +1,108 @@
+use std::ops::Range;
+
+use codespan_reporting::files::Files as _;
+use ecow::{EcoString, EcoVec, eco_format};
+use eframe::egui::{Color32, RichText, ScrollArea, Ui};
+use typst_library::WorldExt;
+use typst_library::diag::{Severity, SourceDiagnostic};
+use typst_syntax::Span;
+
+use crate::SystemWorld;
+
+/// An error or warning with its span resolved in the world it was compiled
+/// in, such that it can be shown after the world moved on.
+pub(super) struct Diagnostic {
+    /// Whether this is an error or a warning.
+    pub severity: Severity,
+    /// The message describing the problem.
+    pub message: EcoString,
+    /// Hints on how to avoid the problem.
+    pub hints: EcoVec<EcoString>,
+    /// The byte range the diagnostic points to in the main file, which is the
+    /// one in the editor. If the problem is in another file, this is where
+    /// the main file leads to it.
+    pub range: Option<Range<usize>>,
+    /// The file, line and column the diagnostic points to, if any. The file
+    /// is left out for the main file.
+    pub location: Option<EcoString>,
+}
+
+impl Diagnostic {
+    /// Resolve a diagnostic of the last compilation in the world.
+    pub fn new(world: &SystemWorld, diagnostic: &SourceDiagnostic) -> Self {
+        let main = world.main();
+        let range = std::iter::once(diagnostic.span)
+            .chain(diagnostic.trace.iter().map(|point| point.span))
+            .filter(|span| span.id() == Some(main))
+            .find_map(|span| world.range(span));
+
+        Self {
+            severity: diagnostic.severity,
+            message: diagnostic.message.clone(),
+            hints: diagnostic.hints.clone(),
+            range,
+            location: locate(world, diagnostic.span),
+        }
+    }
+
+    /// The color the diagnostic is marked with.
+    pub fn color(&self) -> Color32 {
+        match self.severity {
+            Severity::Error => Color32::from_rgb(0xe0, 0x30, 0x30),
+            Severity::Warning => Color32::from_rgb(0xe0, 0x9a, 0x10),
+        }
+    }
+}
+
+/// Describe where a span points to as `file:line:column`, or as `line:column`
+/// in the main file.
+fn locate(world: &SystemWorld, span: Span) -> Option<EcoString> {
+    let id = span.id()?;
+    let range = world.range(span)?;
+    let location = world.location(id, range.start).ok()?;
+    let (line, column) = (location.line_number, location.column_number);
+    Some(if id == world.main() {
+        eco_format!("{line}:{column}")
+    } else {
+        let name = world.name(id).ok()?;
+        eco_format!("{name}:{line}:{column}")
+    })
+}
+
+/// List the diagnostics with their hints. Returns the byte offset in the
+/// main file of the diagnostic that was clicked, if any.
+pub(super) fn panel(ui: &mut Ui, diagnostics: &[Diagnostic]) -> Option<usize> {
+    let mut clicked = None;
+    ScrollArea::vertical()
+        .id_salt("typst-diagnostics")
+        .auto_shrink([false, true])
+        .show(ui, |ui| {
+            for diagnostic in diagnostics {
+                let severity = match diagnostic.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(RichText::new(severity).color(diagnostic.color()).strong());
+                    if let Some(location) = &diagnostic.location {
+                        ui.label(RichText::new(location.as_str()).monospace().weak());
+                    }
+
+                    // Only problems in the editor's file can be jumped to.
+                    let message = ui.selectable_label(false, diagnostic.message.as_str());
+                    if let Some(range) = &diagnostic.range
+                        && message.on_hover_text("Go to the source").clicked()
+                    {
+                        clicked = Some(range.start);
+                    }
+                });
+                for hint in &diagnostic.hints {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.add_space(16.0);
+                        ui.label(RichText::new(format!("hint: {hint}")).weak());
+                    });
+                }
+            }
+        });
+    clicked
+}
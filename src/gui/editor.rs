@@ -0,0 +1,486 @@
+use eframe::egui::text::{CCursor, CCursorRange, LayoutJob, TextFormat};
+use eframe::egui::text_edit::{TextEditOutput, TextEditState};
+use eframe::egui::{
+    Align, Color32, Context, FontId, Id, Key, Label, Margin, Rect, RichText, ScrollArea, Shape,
+    Stroke, TextBuffer, TextEdit, TextStyle, Ui, pos2,
+};
+use typst_syntax::{LinkedNode, Side, SyntaxKind, Tag, highlight};
+
+use super::diagnostics::Diagnostic;
+
+/// How far a line is indented beyond the previous one after an opening
+/// bracket.
+const INDENT: &str = "  ";
+
+/// The width of one zig-zag of the lines under diagnostics.
+const SQUIGGLE_WIDTH: f32 = 3.0;
+
+/// The height of the lines under diagnostics.
+const SQUIGGLE_HEIGHT: f32 = 2.0;
+
+/// A source code editor with Typst syntax highlighting, line numbers and
+/// bracket matching.
+///
+/// Undo and redo are provided by egui's text edit, which records the text
+/// whenever editing pauses.
+pub(super) struct Editor {
+    /// The edited source text.
+    text: String,
+    /// The byte offsets of the brackets matching each other at the cursor.
+    brackets: Option<[usize; 2]>,
+    /// The highlighted text of the last layout, along with the hash of the
+    /// text and brackets it was created for.
+    highlighted: Option<(u128, LayoutJob)>,
+    /// The byte offset to move the cursor to and scroll to the next time the
+    /// editor is shown.
+    jump: Option<usize>,
+    /// The byte offset of the cursor, if nothing is selected.
+    cursor: Option<usize>,
+}
+
+impl Editor {
+    /// Creates an editor for the source text.
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            brackets: None,
+            highlighted: None,
+            jump: None,
+            cursor: None,
+        }
+    }
+
+    /// The edited source text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replace the whole text, such as when a file is dropped onto the
+    /// window.
+    pub fn set_text(&mut self, text: String) {
+        self.text = text;
+        self.brackets = None;
+    }
+
+    /// The byte offset of the cursor as of the last time the editor was
+    /// shown, if nothing is selected.
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+
+    /// Move the cursor to a byte offset in the text and scroll to it.
+    pub fn jump_to(&mut self, offset: usize) {
+        self.jump = Some(offset);
+    }
+
+    /// Show the editor with the diagnostics underlined, returning whether the
+    /// text was edited.
+    pub fn show(&mut self, ui: &mut Ui, diagnostics: &[Diagnostic]) -> bool {
+        let id = Id::new("typst-editor");
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            changed |= self.history_button(ui, id, "⟲ Undo", false);
+            changed |= self.history_button(ui, id, "⟳ Redo", true);
+        });
+        ui.separator();
+
+        ScrollArea::both()
+            .id_salt("typst-editor-scroll")
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                ui.horizontal_top(|ui| {
+                    self.line_numbers(ui);
+                    changed |= self.text_edit(ui, id, diagnostics);
+                });
+            });
+
+        changed
+    }
+
+    /// Show the numbers of the lines next to them.
+    fn line_numbers(&self, ui: &mut Ui) {
+        let count = self.text.split('\n').count();
+        let width = count.to_string().len();
+        let numbers = (1..=count)
+            .map(|line| format!("{line:>width$}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        ui.add(Label::new(RichText::new(numbers).monospace().weak()).selectable(false));
+    }
+
+    /// Show the text itself, returning whether it was edited.
+    fn text_edit(&mut self, ui: &mut Ui, id: Id, diagnostics: &[Diagnostic]) -> bool {
+        let font = TextStyle::Monospace.resolve(ui.style());
+        let dark = ui.visuals().dark_mode;
+        let brackets = self.brackets;
+        let highlighted = &mut self.highlighted;
+        let mut layouter = |ui: &Ui, text: &dyn TextBuffer, _: f32| {
+            let text = text.as_str();
+            let hash = typst_utils::hash128(&(text, brackets, dark, &font));
+            let job = match highlighted {
+                Some((cached, job)) if *cached == hash => job.clone(),
+                _ => {
+                    let job = highlight_text(text, brackets, font.clone(), dark);
+                    *highlighted = Some((hash, job.clone()));
+                    job
+                }
+            };
+            ui.fonts_mut(|fonts| fonts.layout_job(job))
+        };
+
+        let mut output = TextEdit::multiline(&mut self.text)
+            .id(id)
+            .code_editor()
+            .frame(false)
+            .margin(Margin::symmetric(4, 0))
+            .desired_width(f32::INFINITY)
+            .desired_rows(40)
+            .layouter(&mut layouter)
+            .show(ui);
+
+        let changed = output.response.changed();
+        underline(ui, &output, &self.text, diagnostics);
+
+        if let Some(offset) = self.jump.take() {
+            let index = char_index(&self.text, offset);
+            let cursor = CCursor::new(index);
+            output
+                .state
+                .cursor
+                .set_char_range(Some(CCursorRange::one(cursor)));
+            output.state.clone().store(ui.ctx(), id);
+            focus(ui.ctx(), id);
+            let rect = output.galley.pos_from_cursor(cursor);
+            ui.scroll_to_rect(
+                rect.translate(output.galley_pos.to_vec2()),
+                Some(Align::Center),
+            );
+        }
+
+        if changed
+            && ui.input(|i| i.key_pressed(Key::Enter))
+            && let Some(range) = output.cursor_range
+            && let Some(moved) = auto_indent(&mut self.text, range)
+        {
+            output.state.cursor.set_char_range(Some(moved));
+            output.state.clone().store(ui.ctx(), id);
+        }
+
+        let cursor = output
+            .state
+            .cursor
+            .char_range()
+            .filter(CCursorRange::is_empty)
+            .map(|range| range.primary.index);
+        self.cursor = cursor.map(|index| byte_offset(&self.text, index));
+
+        // The brackets are found after the layout, so lay the text out again
+        // if they moved.
+        let brackets = cursor.and_then(|index| matching_brackets(&self.text, index));
+        if brackets != self.brackets {
+            self.brackets = brackets;
+            ui.ctx().request_repaint();
+        }
+
+        changed
+    }
+
+    /// Show a button that undoes or redoes an edit, returning whether it was
+    /// clicked and changed the text.
+    fn history_button(&mut self, ui: &mut Ui, id: Id, label: &str, redo: bool) -> bool {
+        let state = TextEditState::load(ui.ctx(), id);
+        let current = (
+            state
+                .as_ref()
+                .and_then(|state| state.cursor.char_range())
+                .unwrap_or_default(),
+            self.text.clone(),
+        );
+        let enabled = state.as_ref().is_some_and(|state| {
+            let undoer = state.undoer();
+            if redo {
+                undoer.has_redo(&current)
+            } else {
+                undoer.has_undo(&current)
+            }
+        });
+
+        if !ui
+            .add_enabled(enabled, eframe::egui::Button::new(label))
+            .clicked()
+        {
+            return false;
+        }
+        let Some(mut state) = state else {
+            return false;
+        };
+
+        let mut undoer = state.undoer();
+        let restored = if redo {
+            undoer.redo(&current)
+        } else {
+            undoer.undo(&current)
+        };
+        let Some((range, text)) = restored.cloned() else {
+            return false;
+        };
+        state.set_undoer(undoer);
+        state.cursor.set_char_range(Some(range));
+        state.store(ui.ctx(), id);
+        focus(ui.ctx(), id);
+        self.text = text;
+        true
+    }
+}
+
+/// Draw squiggly lines under the ranges of the diagnostics and show their
+/// messages when hovering them.
+///
+/// The ranges refer to the text that was compiled last, so they may be off
+/// while the text is being edited.
+fn underline(ui: &Ui, output: &TextEditOutput, text: &str, diagnostics: &[Diagnostic]) {
+    let pointer = ui.ctx().pointer_hover_pos();
+    for diagnostic in diagnostics {
+        let Some(range) = &diagnostic.range else {
+            continue;
+        };
+
+        // The text is not wrapped, so every line is a row of its own.
+        let (start, end) = (char_index(text, range.start), char_index(text, range.end));
+        let mut line_start = start;
+        for line in text[byte_offset(text, start)..byte_offset(text, end)].split('\n') {
+            let line_end = line_start + line.chars().count();
+            let from = output.galley.pos_from_cursor(CCursor::new(line_start));
+            let to = output.galley.pos_from_cursor(CCursor::new(line_end));
+            let offset = output.galley_pos.to_vec2();
+            let (left, right) = (from.left() + offset.x, to.left() + offset.x);
+            let y = from.bottom() + offset.y - 1.0;
+
+            // Empty ranges, such as a missing closing bracket, still get a
+            // short line.
+            let right = right.max(left + SQUIGGLE_WIDTH * 2.0);
+            ui.painter()
+                .add(squiggle(left, right, y, diagnostic.color()));
+
+            let rect = Rect::from_x_y_ranges(left..=right, from.top() + offset.y..=y + 2.0);
+            if pointer.is_some_and(|pointer| rect.contains(pointer)) {
+                output
+                    .response
+                    .clone()
+                    .on_hover_text_at_pointer(diagnostic.message.as_str());
+            }
+            line_start = line_end + 1;
+        }
+    }
+}
+
+/// A zig-zag line from `left` to `right` just above `y`.
+fn squiggle(left: f32, right: f32, y: f32, color: Color32) -> Shape {
+    let steps = ((right - left) / SQUIGGLE_WIDTH).ceil().max(1.0) as usize;
+    let width = (right - left) / steps as f32;
+    let points = (0..=steps)
+        .map(|step| {
+            let dy = if step % 2 == 0 { 0.0 } else { -SQUIGGLE_HEIGHT };
+            pos2(left + step as f32 * width, y + dy)
+        })
+        .collect();
+    Shape::line(points, Stroke::new(1.0, color))
+}
+
+/// Give the keyboard focus back to the text.
+fn focus(ctx: &Context, id: Id) {
+    ctx.memory_mut(|memory| memory.request_focus(id));
+}
+
+/// Indent a line that was just started by pressing enter like the previous
+/// one, and one level deeper after an opening bracket. Returns where the
+/// cursor moves to.
+fn auto_indent(text: &mut String, range: CCursorRange) -> Option<CCursorRange> {
+    if !range.is_empty() {
+        return None;
+    }
+
+    let cursor = byte_offset(text, range.primary.index);
+    let before = text[..cursor].strip_suffix('\n')?;
+    let line = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+    let mut indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+
+    let opening = line.trim_end().chars().last().and_then(closing_bracket);
+    if opening.is_some() {
+        indent.push_str(INDENT);
+    }
+
+    // Move a closing bracket right after the cursor onto a line of its own.
+    let mut inserted = indent.clone();
+    if let Some(closing) = opening
+        && text[cursor..].starts_with(closing)
+    {
+        inserted.push('\n');
+        inserted.push_str(indent.strip_suffix(INDENT).unwrap_or(&indent));
+    }
+
+    text.insert_str(cursor, &inserted);
+    let index = range.primary.index + indent.chars().count();
+    Some(CCursorRange::one(CCursor::new(index)))
+}
+
+/// The bracket that closes an opening bracket.
+fn closing_bracket(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        _ => None,
+    }
+}
+
+/// Find the bracket right at or before the cursor, given as a character
+/// index, and the one it is paired with. Returns the byte offsets of both.
+fn matching_brackets(text: &str, cursor: usize) -> Option<[usize; 2]> {
+    let root = typst_syntax::parse(text);
+    let root = LinkedNode::new(&root);
+    let cursor = byte_offset(text, cursor);
+
+    [Side::After, Side::Before].into_iter().find_map(|side| {
+        let leaf = root.leaf_at(cursor, side)?;
+        if !is_bracket(leaf.kind()) {
+            return None;
+        }
+
+        // Brackets are the first and last children of the node they enclose.
+        let parent = leaf.parent()?;
+        let mut children = parent.children();
+        let (first, last) = (children.next()?, children.next_back()?);
+        let matched = matches!(
+            (first.kind(), last.kind()),
+            (SyntaxKind::LeftParen, SyntaxKind::RightParen)
+                | (SyntaxKind::LeftBracket, SyntaxKind::RightBracket)
+                | (SyntaxKind::LeftBrace, SyntaxKind::RightBrace)
+        );
+        matched.then(|| [first.offset(), last.offset()])
+    })
+}
+
+/// Whether a node is a parenthesis, a bracket or a brace.
+fn is_bracket(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::LeftParen
+            | SyntaxKind::RightParen
+            | SyntaxKind::LeftBracket
+            | SyntaxKind::RightBracket
+            | SyntaxKind::LeftBrace
+            | SyntaxKind::RightBrace
+    )
+}
+
+/// Lay out source text with colors for its syntax and a background for the
+/// matching brackets.
+fn highlight_text(text: &str, brackets: Option<[usize; 2]>, font: FontId, dark: bool) -> LayoutJob {
+    let root = typst_syntax::parse(text);
+    let mut job = LayoutJob::default();
+    let format = TextFormat::simple(font, default_color(dark));
+    highlight_node(
+        &mut job,
+        &LinkedNode::new(&root),
+        None,
+        &format,
+        brackets,
+        dark,
+    );
+    job
+}
+
+/// Add the leaves of a node to the layout, colored by the innermost
+/// highlighted node they are part of.
+fn highlight_node(
+    job: &mut LayoutJob,
+    node: &LinkedNode,
+    tag: Option<Tag>,
+    format: &TextFormat,
+    brackets: Option<[usize; 2]>,
+    dark: bool,
+) {
+    // Errors are underlined by the diagnostics instead.
+    let tag = highlight(node).filter(|&tag| tag != Tag::Error).or(tag);
+    if node.children().len() > 0 {
+        for child in node.children() {
+            highlight_node(job, &child, tag, format, brackets, dark);
+        }
+        return;
+    }
+
+    let mut format = format.clone();
+    if let Some(tag) = tag {
+        format.color = tag_color(tag, dark);
+        format.italics = tag == Tag::Emph;
+    }
+    if brackets.is_some_and(|brackets| brackets.contains(&node.offset())) {
+        format.background = bracket_background(dark);
+    }
+    job.append(node.text(), 0.0, format);
+}
+
+/// The color of text that isn't highlighted.
+fn default_color(dark: bool) -> Color32 {
+    if dark {
+        Color32::from_gray(220)
+    } else {
+        Color32::from_gray(30)
+    }
+}
+
+/// The background of matching brackets.
+fn bracket_background(dark: bool) -> Color32 {
+    if dark {
+        Color32::from_rgb(70, 80, 100)
+    } else {
+        Color32::from_rgb(200, 220, 255)
+    }
+}
+
+/// The color of a highlighted piece of syntax, after the colors of the Typst
+/// web app.
+fn tag_color(tag: Tag, dark: bool) -> Color32 {
+    let (light, night) = match tag {
+        Tag::Comment => ((0x8a, 0x8a, 0x8a), (0x8a, 0x8a, 0x8a)),
+        Tag::Punctuation => ((0x1d, 0x6c, 0x76), (0x9c, 0xdc, 0xe6)),
+        Tag::Escape => ((0x1d, 0x6c, 0x76), (0x9c, 0xdc, 0xe6)),
+        Tag::Strong => ((0x2b, 0x2b, 0x2b), (0xf0, 0xf0, 0xf0)),
+        Tag::Emph => ((0x2b, 0x2b, 0x2b), (0xf0, 0xf0, 0xf0)),
+        Tag::Link => ((0x1d, 0x6c, 0x76), (0x9c, 0xdc, 0xe6)),
+        Tag::Raw => ((0x81, 0x8e, 0x52), (0xa6, 0xb8, 0x6c)),
+        Tag::Label => ((0x1d, 0x6c, 0x76), (0x9c, 0xdc, 0xe6)),
+        Tag::Ref => ((0x1d, 0x6c, 0x76), (0x9c, 0xdc, 0xe6)),
+        Tag::Heading => ((0x1b, 0x1b, 0x1b), (0xff, 0xff, 0xff)),
+        Tag::ListMarker => ((0x8b, 0x41, 0xb1), (0xd3, 0x9b, 0xf0)),
+        Tag::ListTerm => ((0x8b, 0x41, 0xb1), (0xd3, 0x9b, 0xf0)),
+        Tag::MathDelimiter => ((0x29, 0x8e, 0x0d), (0x7c, 0xd6, 0x5d)),
+        Tag::MathOperator => ((0x1d, 0x6c, 0x76), (0x9c, 0xdc, 0xe6)),
+        Tag::Keyword => ((0xd7, 0x3a, 0x49), (0xff, 0x7b, 0x8a)),
+        Tag::Operator => ((0x1d, 0x6c, 0x76), (0x9c, 0xdc, 0xe6)),
+        Tag::Number => ((0xb6, 0x50, 0x0b), (0xf0, 0xa0, 0x60)),
+        Tag::String => ((0x29, 0x8e, 0x0d), (0x7c, 0xd6, 0x5d)),
+        Tag::Function => ((0x4b, 0x69, 0xc6), (0x8d, 0xaa, 0xff)),
+        Tag::Interpolated => ((0x8b, 0x41, 0xb1), (0xd3, 0x9b, 0xf0)),
+        Tag::Error => ((0xff, 0x41, 0x36), (0xff, 0x6b, 0x62)),
+    };
+    let (r, g, b) = if dark { night } else { light };
+    Color32::from_rgb(r, g, b)
+}
+
+/// The character index of a byte offset into a text. Offsets past the end or
+/// within a character are moved to the end of the text or of the character.
+fn char_index(text: &str, offset: usize) -> usize {
+    text.char_indices()
+        .take_while(|&(start, _)| start < offset)
+        .count()
+}
+
+/// The byte offset of a character index into a text.
+fn byte_offset(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(offset, _)| offset)
+}
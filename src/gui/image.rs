@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use eframe::egui::{
+    Color32, ColorImage, Context, Id, Mesh, Rect, TextureHandle, TextureOptions, Ui, pos2,
+};
+use eframe::epaint::Vertex;
+use typst_library::foundations::{Content, Smart};
+use typst_library::layout::{Frame, FrameItem, Page, Point, Size};
+use typst_library::visualize::Image;
+use typst_syntax::Span;
+
+use super::clip::clip_mesh;
+use super::update::{State, scale};
+
+/// The largest width or height of an image texture, in pixels.
+const MAX_TEXTURE_SIZE: f32 = 4096.0;
+
+/// How many texture resolutions there are per doubling of the zoom. Textures
+/// are only rendered again when the zoom crosses one of these steps.
+const STEPS_PER_OCTAVE: f32 = 4.0;
+
+/// How many pixels the cached textures may have in total. Beyond that, the
+/// ones that were not drawn in the current pass are dropped.
+const MAX_TEXTURE_PIXELS: usize = 1 << 26;
+
+/// The textures of the frames rendered so far, along with the resolution step
+/// they were rendered at and the pass they were last drawn in.
+#[derive(Clone, Default)]
+struct Textures(HashMap<u128, (i32, TextureHandle, u64)>);
+
+/// Render an image of the given size, mapping its points to the screen with
+/// the state.
+///
+/// The image is rasterized with `typst_render`, so that it looks like in the
+/// PNG export, and uploaded as a texture that is drawn onto the transformed
+/// image bounds.
+pub(super) fn render_image(ui: &mut Ui, image: &Image, size: Size, state: State) {
+    let ts = state.ts;
+    let corners = [
+        Point::zero(),
+        Point::with_x(size.x),
+        size.to_point(),
+        Point::with_y(size.y),
+    ]
+    .map(|point| {
+        let point = point.transform(ts);
+        pos2(point.x.to_pt() as f32, point.y.to_pt() as f32)
+    });
+
+    if !ui.is_rect_visible(Rect::from_points(&corners)) {
+        return;
+    }
+
+    let pixel_per_pt = scale(ts) as f32 * ui.ctx().pixels_per_point();
+    let key = typst_utils::hash128(&(image, size));
+    let frame = || {
+        let mut frame = Frame::soft(size);
+        let item = FrameItem::Image(image.clone(), size, Span::detached());
+        frame.push(Point::zero(), item);
+        frame
+    };
+    let options = TextureOptions::LINEAR;
+    let Some(texture) = cached_texture(ui.ctx(), key, size, frame, pixel_per_pt, options) else {
+        return;
+    };
+
+    let uvs = [
+        pos2(0.0, 0.0),
+        pos2(1.0, 0.0),
+        pos2(1.0, 1.0),
+        pos2(0.0, 1.0),
+    ];
+    let mut mesh = Mesh::with_texture(texture.id());
+    for (pos, uv) in corners.into_iter().zip(uvs) {
+        mesh.vertices.push(Vertex {
+            pos,
+            uv,
+            color: Color32::WHITE,
+        });
+    }
+    mesh.add_triangle(0, 1, 2);
+    mesh.add_triangle(0, 2, 3);
+    ui.painter().add(clip_mesh(mesh, state.clips));
+}
+
+/// Get a texture of a frame with the given size that has about the given
+/// number of pixels per point, rendering the frame if necessary. The key
+/// identifies the frame.
+pub(super) fn cached_texture(
+    ctx: &Context,
+    key: u128,
+    size: Size,
+    frame: impl FnOnce() -> Frame,
+    pixel_per_pt: f32,
+    options: TextureOptions,
+) -> Option<TextureHandle> {
+    let longest = size.x.max(size.y).to_pt() as f32;
+    if !(longest > 0.0 && pixel_per_pt > 0.0) {
+        return None;
+    }
+
+    let pixel_per_pt = pixel_per_pt.min(MAX_TEXTURE_SIZE / longest);
+    let step = (pixel_per_pt.log2() * STEPS_PER_OCTAVE).ceil() as i32;
+    let id = Id::new("typst-textures");
+    let pass = ctx.cumulative_pass_nr();
+
+    let cached = ctx.data_mut(|data| {
+        let (_, texture, drawn) = data
+            .get_temp_mut_or_default::<Textures>(id)
+            .0
+            .get_mut(&key)
+            .filter(|(cached, _, _)| *cached == step)?;
+        *drawn = pass;
+        Some(texture.clone())
+    });
+    if cached.is_some() {
+        return cached;
+    }
+
+    let resolution = (step as f32 / STEPS_PER_OCTAVE).exp2();
+    let page = Page {
+        frame: frame(),
+        fill: Smart::Custom(None),
+        numbering: None,
+        supplement: Content::empty(),
+        number: 1,
+    };
+    let pixmap = typst_render::render(&page, resolution);
+    let color_image = ColorImage::from_rgba_premultiplied(
+        [pixmap.width() as usize, pixmap.height() as usize],
+        pixmap.data(),
+    );
+    let texture = ctx.load_texture("typst-frame", color_image, options);
+
+    ctx.data_mut(|data| {
+        let textures = data.get_temp_mut_or_default::<Textures>(id);
+        textures.0.insert(key, (step, texture.clone(), pass));
+        textures.evict(pass);
+    });
+    Some(texture)
+}
+
+impl Textures {
+    /// Drop the textures that were not drawn in the current pass if they
+    /// have too many pixels. Their memory is freed once the last handle is
+    /// dropped.
+    fn evict(&mut self, pass: u64) {
+        let pixels: usize = self
+            .0
+            .values()
+            .map(|(_, texture, _)| texture.size().iter().product::<usize>())
+            .sum();
+        if pixels > MAX_TEXTURE_PIXELS {
+            self.0.retain(|_, (_, _, drawn)| *drawn == pass);
+        }
+    }
+}
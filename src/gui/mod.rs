@@ -1,234 +1,181 @@
+mod clip;
+mod compiler;
+mod diagnostics;
+mod editor;
+mod image;
+mod pages;
+mod paint;
 mod shapes;
+mod sync;
+#[cfg(test)]
+mod testing;
 mod text;
+mod tree;
 mod update;
 
-use eframe::egui::{FontData, FontDefinitions};
+use eframe::egui::{Context, FontData};
 use eframe::epaint::FontFamily;
+use eframe::epaint::text::{FontInsert, FontPriority, InsertFontFamily};
 use std::path::PathBuf;
-use typst_library::layout::Frame;
+use typst_library::layout::{Frame, PagedDocument};
+use typst_library::text::Font;
+use typst_syntax::Source;
+
+use crate::args::Pages;
+use crate::fonts::FontNames;
+use compiler::{Compiler, Job};
+use diagnostics::Diagnostic;
+use editor::Editor;
+use pages::{PageNumbers, PageView};
+use sync::SpanIndex;
 use typst_library::layout::FrameItem::{Group, Text};
 
-use std::sync::Arc;
-
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-
-use ttf_parser::{Face, name_id};
-
-fn hash_u64(data: &[u8]) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    data.hash(&mut hasher);
-    hasher.finish()
-}
+use ttf_parser::Face;
 
+/// What the side panel shows.
+#[derive(Copy, Clone, Eq, PartialEq)]
 enum View {
+    /// The items of the laid out pages, for debugging the layout.
     Tree,
+    /// The source of the document.
     Text,
 }
 
+/// An item of the laid out document in the frame-tree inspector.
 enum TreeNode {
-    Leaf(String),
-    Node(Vec<TreeNode>),
+    /// An item without children, such as a text run or a shape.
+    Leaf(tree::Entry),
+    /// A page or group along with the items within it.
+    Node(tree::Entry, Vec<TreeNode>),
 }
 
 struct MyApp {
-    page: Option<Frame>,
-    renderer: super::Renderer,
+    document: Option<PagedDocument>,
+    /// The numbers of the shown pages in the whole document.
+    numbers: PageNumbers,
+    /// The boxes of the shown document's items by the spans that produced
+    /// them.
+    spans: SpanIndex,
+    page_view: PageView,
+    compiler: Compiler,
+    /// The errors and warnings of the last compilation.
+    diagnostics: Vec<Diagnostic>,
+    /// The main file as of the shown document.
+    source: Option<Source>,
+    /// The editor's cursor position that the preview was last synced to.
+    synced: Option<usize>,
     display: bool,
-    source: Option<String>,
     view: View,
+    /// The inspected items of the shown document, built when first needed.
     tree: Option<TreeNode>,
-    input: String,
-    font_definitions: FontDefinitions,
+    editor: Editor,
+    /// Whether a font of a document was registered for Chinese text.
+    cjk_fallback: bool,
 }
 
 impl MyApp {
-    fn new(renderer: super::Renderer, input: String) -> Self {
+    fn new(compiler: Compiler, input: String) -> Self {
         MyApp {
-            page: None,
-            renderer,
-            display: true,
+            document: None,
+            numbers: PageNumbers::default(),
+            spans: SpanIndex::default(),
+            page_view: PageView::default(),
+            compiler,
+            diagnostics: Vec::new(),
             source: None,
+            synced: None,
+            display: true,
             view: View::Text,
             tree: None,
             // input: "#v(100pt)\n#line(length:100%)\n= 你好，世界233".into(),
-            input,
-            font_definitions: FontDefinitions::empty(),
+            editor: Editor::new(input),
+            cjk_fallback: false,
         }
     }
 }
 
-pub(crate) fn run(file: Option<PathBuf>, mut renderer: super::Renderer) {
+pub(crate) fn run(file: Option<PathBuf>, pages: Option<&[Pages]>, renderer: super::Renderer) {
     use std::fs::File;
     use std::io::Read;
 
-    let options = eframe::NativeOptions::default();
+    // The document's shapes and glyphs are drawn as meshes, which egui
+    // doesn't anti-alias, so let the graphics card smooth their edges.
+    let options = eframe::NativeOptions {
+        multisampling: 4,
+        ..Default::default()
+    };
 
-    let page = file.as_ref().map(|x| renderer.render_from_path(&x));
-    let input = file.map_or_else(String::new, |x| {
+    let input = file.as_ref().map_or_else(String::new, |x| {
         let mut f = File::open(x).unwrap();
         let mut ret = String::new();
         f.read_to_string(&mut ret).unwrap();
         ret
     });
-    let mut app = MyApp::new(renderer, input);
-
-    let mut font_definitions = FontDefinitions::default();
-    // if page.is_none() && !app.input.is_empty() {
-    //     let page = app.renderer.render_from_vec(app.input.as_bytes().into());
-    //     collect_font_from_frame(&mut app.font_definitions, &page);
-    //     app.page = Some(page);
-    // }
-
-    if let Some(page) = &page {
-        collect_font_from_frame(&mut font_definitions, &page);
-    }
-    app.page = page;
+    let pages = pages.map(<[_]>::to_vec);
 
     eframe::run_native(
         "litter typer",
         options,
         Box::new(move |cc| {
-            cc.egui_ctx.set_fonts(font_definitions);
-            Ok(Box::new(app))
+            // The document is compiled in the background and shows up once
+            // it's ready.
+            let mut compiler = Compiler::spawn(renderer, pages, cc.egui_ctx.clone());
+            if let Some(file) = file {
+                compiler.submit(Job::Path(file));
+            }
+            Ok(Box::new(MyApp::new(compiler, input)))
         }),
     )
     .unwrap()
 }
 
-fn make(c: char, font_data: &[u8]) -> [[char; 64]; 32] {
-    use rusttype::{Font, Scale, point};
-    // This only succeeds if collection consists of one font
-    let font = Font::try_from_bytes(font_data).expect("Error constructing Font");
-
-    // The font size to use
-    let scale = Scale { x: 64.0, y: 32.0 };
-
-    let v_metrics = font.v_metrics(scale);
-    // println!("v_metrics {v_metrics:?}");
-
-    let mut data = [[' '; 64]; 32];
-    let cursor = point(0.0, v_metrics.ascent);
-    let glyph = font.glyph(c);
-    let scaled = glyph.scaled(scale);
-    let glyph = scaled.positioned(cursor);
-    if let Some(bounding_box) = glyph.pixel_bounding_box() {
-        // Draw the glyph into the image per-pixel by using the draw closure
-        glyph.draw(|x, y, v| {
-            let x = x as i32 + bounding_box.min.x + 1;
-            let y = y as i32 + bounding_box.min.y;
-            if x >= 0 && y >= 0 && x < 64 && y < 32 {
-                let x = x as usize;
-                let y = y as usize;
-                let print = if v >= 0.5 {
-                    '@'
-                } else if v >= 0.25 {
-                    '$'
-                } else if v >= 0.125 {
-                    '+'
-                } else {
-                    ' '
-                };
-                data[y][x] = print;
-            } else {
-                println!("Out of bounds: ({}, {}) limit (64, 32)", x, y,);
-            }
-        });
-    }
-    data
+/// Let the ui and the editor fall back to a font of the document for Chinese
+/// text, which egui's own fonts lack. Returns whether the document has such a
+/// font.
+fn register_cjk_fallback(ctx: &Context, document: &PagedDocument) -> bool {
+    let Some(font) = document
+        .pages
+        .iter()
+        .find_map(|page| find_cjk_font(&page.frame))
+    else {
+        return false;
+    };
+
+    log_font_info("chinese", font.ttf());
+    let mut data = FontData::from_owned(font.data().to_vec());
+    data.index = font.index();
+    let families = [FontFamily::Proportional, FontFamily::Monospace]
+        .into_iter()
+        .map(|family| InsertFontFamily {
+            family,
+            priority: FontPriority::Lowest,
+        })
+        .collect();
+    ctx.add_font(FontInsert::new("chinese", data, families));
+    true
 }
 
-pub(crate) fn collect_font_from_frame(defs: &mut FontDefinitions, frame: &Frame) {
-    for (_, item) in frame.items() {
-        match item {
-            Text(text) => {
-                let font_hash = hash_u64(text.font.data().as_slice());
-                let font_name = format!("font-{}", font_hash);
-
-                if !defs.font_data.contains_key(&font_name) {
-                    println!("=== font {font_name}");
-                    let data = make('2', text.font.data().as_slice());
-                    let mut empty = true;
-                    for line in data {
-                        for item in line {
-                            if item != ' ' {
-                                empty = false;
-                            }
-                            print!("{item}");
-                        }
-                        println!();
-                    }
-                    println!("'2' is {}", if empty { "empty" } else { "not empty" });
-
-                    let face = Face::parse(text.font.data().as_slice(), 0).unwrap();
-                    print_font_info(&face);
-                    if char_in_font(&face, '你') {
-                        defs.font_data.insert(
-                            "chinese".to_owned(),
-                            Arc::new(FontData::from_owned(text.font.data().to_vec())),
-                        );
-                        defs.families
-                            .entry(FontFamily::Proportional)
-                            .or_default()
-                            .insert(0, "chinese".to_owned());
-                    }
-                    println!("##done");
-                    defs.font_data.insert(
-                        font_name.to_owned(),
-                        Arc::new(FontData::from_owned(text.font.data().to_vec())),
-                    );
-                    println!("#font family {font_name}");
-                    defs.families
-                        .entry(FontFamily::Name(font_name.clone().into()))
-                        .or_default()
-                        .insert(0, font_name.to_owned());
-                }
-            }
-            Group(group) => collect_font_from_frame(defs, &group.frame),
-            _ => (),
-        }
-    }
+/// Find a font with Chinese glyphs among the text of a frame and its groups.
+fn find_cjk_font(frame: &Frame) -> Option<&Font> {
+    frame.items().find_map(|(_, item)| match item {
+        Text(text) if text.font.ttf().glyph_index('你').is_some() => Some(&text.font),
+        Group(group) => find_cjk_font(&group.frame),
+        _ => None,
+    })
 }
 
-fn char_in_font(face: &Face, ch: char) -> bool {
-    face.glyph_index(ch).is_some()
-}
-
-pub fn print_font_info(face: &Face) {
-    if face.glyph_index('2').is_none() {
-        println!("damn, '2' is not present");
-    } else {
-        println!("'2' is present");
-    }
-
-    let names = face.names();
-
-    let family = names
-        .get(name_id::TYPOGRAPHIC_FAMILY)
-        .or_else(|| names.get(name_id::FAMILY))
-        .map(|x| x.name)
-        .unwrap_or(b"?");
-    let subfamily = names
-        .get(name_id::TYPOGRAPHIC_SUBFAMILY)
-        .or_else(|| names.get(name_id::SUBFAMILY))
-        .map(|x| x.name)
-        .unwrap_or(b"?");
-    let full_name = names
-        .get(name_id::FULL_NAME)
-        .map(|x| x.name)
-        .unwrap_or(b"?");
-    let postscript = names
-        .get(name_id::POST_SCRIPT_NAME)
-        .map(|x| x.name)
-        .unwrap_or(b"?");
-
-    println!(
-        "size {:.2}MB",
-        face.raw_face().data.len() as f64 / 1_000_000.0
+/// Log the names and the size of a font and whether it has a glyph for '2'.
+pub fn log_font_info(name: &str, face: &Face) {
+    let names = FontNames::new(face);
+    let unknown = || "?".to_owned();
+    tracing::debug!(
+        font = name,
+        family = names.family.unwrap_or_else(unknown),
+        subfamily = names.subfamily.unwrap_or_else(unknown),
+        full_name = names.full_name.unwrap_or_else(unknown),
+        postscript = names.postscript.unwrap_or_else(unknown),
+        size = format_args!("{:.2}MB", face.raw_face().data.len() as f64 / 1_000_000.0),
+        has_two = face.glyph_index('2').is_some(),
+        "registered font",
     );
-
-    println!("Family     : {}", String::from_utf8_lossy(family));
-    println!("Subfamily  : {}", String::from_utf8_lossy(subfamily));
-    println!("Full name  : {}", String::from_utf8_lossy(full_name));
-    println!("PostScript : {}", String::from_utf8_lossy(postscript));
 }
@@ -0,0 +1,345 @@
+use eframe::egui::{self, Color32, Rect, Sense, Stroke, StrokeKind, Ui, Vec2, vec2};
+use typst_library::layout::{Abs, PageRanges, PagedDocument, Point, Ratio, Transform};
+
+use super::shapes::Shapes as _;
+use super::sync::Region;
+use super::update::{State, render_frame};
+
+/// The gap between two pages and between the pages and the border of the
+/// view, in logical pixels.
+const PAGE_GAP: f32 = 16.0;
+
+/// The smallest and largest supported zoom factors.
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 4.0;
+
+/// The factor by which the zoom buttons change the zoom.
+const ZOOM_STEP: f32 = 1.25;
+
+/// Shows the pages of a document stacked on top of each other.
+///
+/// Only the pages that intersect the visible part of the view are painted, so
+/// that long documents stay responsive. The view can be zoomed with the
+/// buttons or Ctrl and the mouse wheel, and panned by dragging.
+pub struct PageView {
+    /// The page at the top of the view, zero-based.
+    current: usize,
+    /// The page number entered into the "go to page" field, one-based and
+    /// counting all pages of the document, not only the shown ones.
+    target: usize,
+    /// A page to scroll to in the next frame, zero-based.
+    scroll_to: Option<usize>,
+    /// How many logical pixels one point takes up.
+    zoom: f32,
+    /// A zoom change to apply in the next frame, along with the position
+    /// within the view that should stay in place. The center of the view
+    /// stays in place if there is no position.
+    zoom_to: Option<(f32, Option<Vec2>)>,
+    /// A request to fit the pages into the view in the next frame.
+    fit: Option<Fit>,
+    /// A scroll offset to apply in the next frame.
+    offset: Option<Vec2>,
+    /// The regions of the pages that belong to the source at the editor's
+    /// cursor.
+    highlights: Vec<Region>,
+    /// The region of the item under the pointer in the frame-tree inspector.
+    hovered: Option<Region>,
+    /// A region to scroll to in the next frame.
+    reveal: Option<Region>,
+}
+
+/// The numbers of the shown pages in the whole document, which differ from
+/// their positions when only some pages are selected.
+#[derive(Default)]
+pub struct PageNumbers {
+    /// The one-based number of each shown page, in ascending order.
+    numbers: Vec<usize>,
+    /// How many pages the whole document has.
+    total: usize,
+}
+
+impl PageNumbers {
+    /// Keep only the selected pages of a document (all of them if there is
+    /// no selection) and remember their numbers.
+    pub fn select(document: &mut PagedDocument, ranges: Option<&PageRanges>) -> Self {
+        let total = document.pages.len();
+        let selected = |index| ranges.is_none_or(|ranges| ranges.includes_page_index(index));
+        let numbers = (1..=total).filter(|number| selected(number - 1)).collect();
+        let mut index = 0;
+        document.pages.retain(|_| {
+            index += 1;
+            selected(index - 1)
+        });
+        Self { numbers, total }
+    }
+
+    /// The number of the shown page at the index.
+    pub fn number(&self, index: usize) -> usize {
+        self.numbers.get(index).copied().unwrap_or(index + 1)
+    }
+
+    /// How many pages the whole document has.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The index of the shown page with the number, or of the next shown
+    /// one if that page isn't shown. The last page stands in for numbers
+    /// beyond it.
+    fn index(&self, number: usize) -> Option<usize> {
+        let last = self.numbers.len().checked_sub(1)?;
+        Some(self.numbers.partition_point(|&n| n < number).min(last))
+    }
+}
+
+/// How to fit the pages into the view.
+#[derive(Copy, Clone)]
+enum Fit {
+    /// Make the widest page as wide as the view.
+    Width,
+    /// Make the current page fit into the view entirely.
+    Page,
+}
+
+impl Default for PageView {
+    fn default() -> Self {
+        Self {
+            current: 0,
+            target: 1,
+            scroll_to: None,
+            zoom: 1.0,
+            zoom_to: None,
+            fit: None,
+            offset: None,
+            highlights: vec![],
+            hovered: None,
+            reveal: None,
+        }
+    }
+}
+
+impl PageView {
+    /// Show the page counter and the controls to move between pages and to
+    /// zoom.
+    pub fn navigation(&mut self, ui: &mut Ui, numbers: &PageNumbers) {
+        let shown = numbers.numbers.len();
+        let total = numbers.total;
+        ui.horizontal(|ui| {
+            let previous = egui::Button::new("◀");
+            if ui.add_enabled(self.current > 0, previous).clicked() {
+                self.scroll_to = Some(self.current - 1);
+            }
+
+            if shown == 0 {
+                ui.label("No pages");
+            } else {
+                let number = numbers.number(self.current);
+                ui.label(format!("Page {number} of {total}"));
+            }
+
+            let next = egui::Button::new("▶");
+            if ui.add_enabled(self.current + 1 < shown, next).clicked() {
+                self.scroll_to = Some(self.current + 1);
+            }
+
+            ui.separator();
+            ui.label("Go to page");
+            let field = ui.add(egui::DragValue::new(&mut self.target).range(1..=total.max(1)));
+            let submitted = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Go").clicked() || submitted {
+                self.scroll_to = numbers.index(self.target);
+            }
+
+            ui.separator();
+            let zoom_out = egui::Button::new("−");
+            if ui.add_enabled(self.zoom > MIN_ZOOM, zoom_out).clicked() {
+                self.zoom_to = Some((self.zoom / ZOOM_STEP, None));
+            }
+            if ui.button(format!("{:.0}%", self.zoom * 100.0)).clicked() {
+                self.zoom_to = Some((1.0, None));
+            }
+            let zoom_in = egui::Button::new("+");
+            if ui.add_enabled(self.zoom < MAX_ZOOM, zoom_in).clicked() {
+                self.zoom_to = Some((self.zoom * ZOOM_STEP, None));
+            }
+            if ui.button("Fit width").clicked() {
+                self.fit = Some(Fit::Width);
+            }
+            if ui.button("Fit page").clicked() {
+                self.fit = Some(Fit::Page);
+            }
+        });
+    }
+
+    /// Mark regions of the pages, scrolling to the first one if it isn't
+    /// visible and `reveal` is set.
+    pub fn highlight(&mut self, regions: Vec<Region>, reveal: bool) {
+        if reveal && let Some(region) = regions.first() {
+            self.reveal = Some(*region);
+        }
+        self.highlights = regions;
+    }
+
+    /// Mark the region of the item under the pointer in the inspector,
+    /// scrolling to it if `reveal` is set.
+    pub fn hover(&mut self, region: Option<Region>, reveal: bool) {
+        if reveal && region.is_some() {
+            self.reveal = region;
+        }
+        self.hovered = region;
+    }
+
+    /// Show the pages of the document. Returns the index of the page that was
+    /// clicked, if any, and where on the page it was clicked.
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        document: &PagedDocument,
+        display: bool,
+    ) -> Option<(usize, Point)> {
+        let zoom = self.zoom;
+
+        // The vertical position of each page's top edge within the stack.
+        let mut tops = Vec::with_capacity(document.pages.len());
+        let mut height = PAGE_GAP;
+        let mut width: f32 = 0.0;
+        for page in &document.pages {
+            let size = page.frame.size();
+            tops.push(height);
+            height += size.y.to_pt() as f32 * zoom + PAGE_GAP;
+            width = width.max(size.x.to_pt() as f32);
+        }
+
+        if let Some(fit) = self.fit.take() {
+            let available = ui.available_size() - Vec2::splat(2.0 * PAGE_GAP);
+            let by_width = available.x / width;
+            let factor = match (fit, document.pages.get(self.current)) {
+                (Fit::Page, Some(page)) => {
+                    by_width.min(available.y / page.frame.height().to_pt() as f32)
+                }
+                _ => by_width,
+            };
+            if factor.is_finite() {
+                self.zoom_to = Some((factor, None));
+            }
+        }
+
+        let mut area = egui::ScrollArea::both().auto_shrink(false);
+        if let Some(offset) = self.offset.take() {
+            area = area.scroll_offset(offset);
+        } else if let Some(&top) = self.scroll_to.take().and_then(|index| tops.get(index)) {
+            area = area.vertical_scroll_offset(top - PAGE_GAP);
+        }
+
+        let mut clicked = None;
+        area.show_viewport(ui, |ui, viewport| {
+            let size = vec2(width * zoom + 2.0 * PAGE_GAP, height).max(viewport.size());
+            let (rect, response) = ui.allocate_exact_size(size, Sense::click());
+
+            // The top left corner of a page on the screen.
+            let origin = |index: usize| {
+                let page_width = document.pages[index].frame.width().to_pt() as f32 * zoom;
+                rect.min + vec2((size.x - page_width) / 2.0, tops[index])
+            };
+            // Map a box on a page to the screen.
+            let to_screen = |region: &Region| {
+                let min = origin(region.page);
+                let corner = |point: Point| {
+                    min + vec2(point.x.to_pt() as f32, point.y.to_pt() as f32) * zoom
+                };
+                Rect::from_two_pos(corner(region.min), corner(region.max))
+            };
+
+            if let Some(pointer) = response.clicked().then(|| response.interact_pointer_pos()) {
+                clicked = pointer.and_then(|pointer| {
+                    let index = tops.partition_point(|&top| top <= pointer.y - rect.min.y);
+                    let index = index.checked_sub(1)?;
+                    let local = (pointer - origin(index)) / zoom;
+                    Some((
+                        index,
+                        Point::new(Abs::pt(local.x as f64), Abs::pt(local.y as f64)),
+                    ))
+                });
+            }
+
+            if let Some(region) = self.reveal.take()
+                && region.page < tops.len()
+            {
+                ui.scroll_to_rect(to_screen(&region).expand(PAGE_GAP), None);
+            }
+
+            // Zoom around the pointer with Ctrl and the mouse wheel.
+            let delta = ui.input(|i| i.zoom_delta());
+            if delta != 1.0
+                && let Some(pointer) = response.hover_pos()
+            {
+                let anchor = pointer - (rect.min + viewport.min.to_vec2());
+                self.zoom_to = Some((zoom * delta, Some(anchor)));
+            }
+
+            // Keep the anchor at the same position within the view while
+            // the pages grow or shrink around it.
+            if let Some((factor, anchor)) = self.zoom_to.take() {
+                let factor = factor.clamp(MIN_ZOOM, MAX_ZOOM);
+                let anchor = anchor.unwrap_or(viewport.size() / 2.0);
+                let offset = (viewport.min.to_vec2() + anchor) * (factor / zoom) - anchor;
+                self.offset = Some(offset.max(Vec2::ZERO));
+                self.zoom = factor;
+                ui.ctx().request_repaint();
+            }
+
+            // The current page is the one at the top of the view.
+            self.current = tops
+                .partition_point(|&top| top <= viewport.min.y + PAGE_GAP)
+                .saturating_sub(1);
+
+            // Only the pages from the last one starting above the viewport to
+            // the last one starting within it can be visible.
+            let first = tops
+                .partition_point(|&top| top <= viewport.min.y)
+                .saturating_sub(1);
+            let last = tops.partition_point(|&top| top < viewport.max.y);
+
+            for (index, page) in document.pages.iter().enumerate().take(last).skip(first) {
+                let page_size = page.frame.size();
+                let w = page_size.x.to_pt() as f32 * zoom;
+                let h = page_size.y.to_pt() as f32 * zoom;
+                let min = origin(index);
+                if !ui.is_rect_visible(Rect::from_min_size(min, vec2(w, h))) {
+                    continue;
+                }
+
+                let (x, y) = (min.x as f64, min.y as f64);
+                ui.draw_rectangle(x, y, w as f64, h as f64, Color32::WHITE);
+                ui.draw_rectangle_lines(x, y, w as f64, h as f64, 1.0, Color32::from_gray(160));
+
+                // Map points on the page to the screen and keep everything
+                // within the page, like in the exported images.
+                let scale = Ratio::new(zoom as f64);
+                let ts = Transform::translate(Abs::pt(x), Abs::pt(y))
+                    .pre_concat(Transform::scale(scale, scale));
+                let previous = ui.clip_rect();
+                ui.set_clip_rect(previous.intersect(Rect::from_min_size(min, vec2(w, h))));
+                render_frame(ui, &page.frame, State::new(page.frame.size(), ts), display);
+                ui.set_clip_rect(previous);
+            }
+
+            let fill = Color32::from_rgba_unmultiplied(255, 200, 0, 48);
+            let stroke = Stroke::new(1.0, Color32::from_rgb(230, 160, 0));
+            for region in self.highlights.iter().filter(|r| r.page < tops.len()) {
+                let rect = to_screen(region).expand(1.0);
+                ui.painter()
+                    .rect(rect, 2.0, fill, stroke, StrokeKind::Outside);
+            }
+
+            if let Some(region) = self.hovered.filter(|r| r.page < tops.len()) {
+                let fill = Color32::from_rgba_unmultiplied(0, 120, 255, 40);
+                let stroke = Stroke::new(1.0, Color32::from_rgb(0, 100, 230));
+                ui.painter()
+                    .rect(to_screen(&region), 0.0, fill, stroke, StrokeKind::Outside);
+            }
+        });
+
+        clicked
+    }
+}
@@ -0,0 +1,331 @@
+use eframe::egui::{Color32, Context, Mesh, Pos2, TextureOptions, TextureWrapMode, Ui, pos2};
+use eframe::epaint::Vertex;
+use tiny_skia as sk;
+use typst_library::layout::{Abs, Frame, Point, Size};
+use typst_library::visualize::{Color, FillRule, Gradient, Paint, RelativeTo, Tiling};
+
+use super::clip::clip_mesh;
+use super::image::cached_texture;
+use super::shapes::{TOLERANCE, tessellate};
+use super::update::{State, scale};
+
+/// The longest edge of a triangle in a mesh with a gradient, in logical
+/// pixels. The gradient is sampled at the corners of the triangles and
+/// interpolated linearly between them.
+const MAX_GRADIENT_EDGE: f32 = 3.0;
+
+/// How often a triangle may be split up to honour the longest edge.
+const MAX_SPLITS: u32 = 8;
+
+/// Where a paint is placed on an item.
+#[derive(Copy, Clone)]
+pub(super) struct Placement {
+    /// The top left corner of the box that paints relative to the item
+    /// itself stretch over, in the item's coordinates.
+    pub origin: Point,
+    /// The size of that box.
+    pub size: Size,
+    /// Whether the item is text, which makes paints relative to the parent
+    /// by default.
+    pub on_text: bool,
+}
+
+impl Placement {
+    /// Places a paint on the box of the given size at the item's origin.
+    pub fn new(size: Size, on_text: bool) -> Self {
+        Self {
+            origin: Point::zero(),
+            size,
+            on_text,
+        }
+    }
+}
+
+/// Converts a Typst color in any color space into an egui color.
+pub(super) fn to_color32(color: Color) -> Color32 {
+    let (r, g, b, a) = color.to_rgb().into_format::<u8, u8>().into_components();
+    Color32::from_rgba_unmultiplied(r, g, b, a)
+}
+
+/// Fill a path, given in screen coordinates, with a paint and clip it to the
+/// state's polygons. Unlike egui's own shapes, the path may be concave and
+/// intersect itself.
+pub(super) fn fill_path(
+    ui: &mut Ui,
+    path: &sk::Path,
+    rule: FillRule,
+    paint: &Paint,
+    state: State,
+    placement: Placement,
+) {
+    if let Some(mesh) = tessellate(path, rule, Color32::WHITE, TOLERANCE) {
+        let mesh = paint_mesh(ui.ctx(), mesh, paint, state, placement);
+        ui.painter().add(clip_mesh(mesh, state.clips));
+    }
+}
+
+/// Color a mesh, given in screen coordinates, with a paint.
+pub(super) fn paint_mesh(
+    ctx: &Context,
+    mut mesh: Mesh,
+    paint: &Paint,
+    state: State,
+    placement: Placement,
+) -> Mesh {
+    match paint {
+        Paint::Solid(color) => {
+            let color = to_color32(*color);
+            for vertex in &mut mesh.vertices {
+                vertex.color = color;
+            }
+            mesh
+        }
+        Paint::Gradient(gradient) => paint_gradient(mesh, gradient, state, placement),
+        Paint::Tiling(tiling) => paint_tiling(ctx, mesh, tiling, state, placement),
+    }
+}
+
+/// Color a mesh with a gradient by sampling it at the corners of small
+/// enough triangles.
+fn paint_gradient(mesh: Mesh, gradient: &Gradient, state: State, placement: Placement) -> Mesh {
+    let (ts, origin, size) = match gradient.unwrap_relative(placement.on_text) {
+        RelativeTo::Self_ => (state.ts, placement.origin, placement.size),
+        RelativeTo::Parent => (state.container_ts, Point::zero(), state.container_size),
+    };
+
+    let mut mesh = subdivide(mesh, MAX_GRADIENT_EDGE);
+    let Some(inverse) = ts.invert() else {
+        return mesh;
+    };
+
+    // Boxes with a negative size extend to the left or top of their origin,
+    // but the gradient is still laid out from their start to their end.
+    let (w, h) = (size.x.to_pt() as f32, size.y.to_pt() as f32);
+    let extent = (w.abs().max(f32::EPSILON), h.abs().max(f32::EPSILON));
+    for vertex in &mut mesh.vertices {
+        let point = to_point(vertex.pos).transform(inverse) - origin;
+        let (x, y) = (point.x.to_pt() as f32, point.y.to_pt() as f32);
+        let color = gradient.sample_at((x * w.signum(), y * h.signum()), extent);
+        vertex.color = to_color32(color);
+    }
+    mesh
+}
+
+/// Color a mesh with a tiling by texturing it with a rendering of one tile
+/// that repeats.
+fn paint_tiling(
+    ctx: &Context,
+    mut mesh: Mesh,
+    tiling: &Tiling,
+    state: State,
+    placement: Placement,
+) -> Mesh {
+    let (ts, origin) = match tiling.unwrap_relative(placement.on_text) {
+        RelativeTo::Self_ => (state.ts, placement.origin),
+        RelativeTo::Parent => (state.container_ts, Point::zero()),
+    };
+    let Some(inverse) = ts.invert() else {
+        return mesh;
+    };
+
+    // The spacing between the tiles is part of the texture, so that it can
+    // simply be repeated.
+    let size = tiling.size() + tiling.spacing();
+    let frame = || {
+        let mut frame = Frame::soft(size);
+        frame.push_frame(Point::zero(), tiling.frame().clone());
+        frame
+    };
+    let options = TextureOptions {
+        wrap_mode: TextureWrapMode::Repeat,
+        ..TextureOptions::LINEAR
+    };
+    let key = typst_utils::hash128(tiling);
+    let pixel_per_pt = scale(ts) as f32 * ctx.pixels_per_point();
+    let Some(texture) = cached_texture(ctx, key, size, frame, pixel_per_pt, options) else {
+        return mesh;
+    };
+
+    mesh.texture_id = texture.id();
+    for vertex in &mut mesh.vertices {
+        let point = to_point(vertex.pos).transform(inverse) - origin;
+        vertex.uv = pos2((point.x / size.x) as f32, (point.y / size.y) as f32);
+        vertex.color = Color32::WHITE;
+    }
+    mesh
+}
+
+/// Split the triangles of a mesh until none of their edges is longer than
+/// the given length.
+fn subdivide(mesh: Mesh, max_edge: f32) -> Mesh {
+    fn split(out: &mut Mesh, [a, b, c]: [Vertex; 3], max_edge: f32, depth: u32) {
+        let longest = [(a, b), (b, c), (c, a)]
+            .into_iter()
+            .map(|(from, to)| from.pos.distance(to.pos))
+            .fold(0.0, f32::max);
+
+        if longest <= max_edge || depth == MAX_SPLITS {
+            let start = out.vertices.len() as u32;
+            out.vertices.extend([a, b, c]);
+            out.add_triangle(start, start + 1, start + 2);
+            return;
+        }
+
+        let mid = |from: Vertex, to: Vertex| Vertex {
+            pos: from.pos.lerp(to.pos, 0.5),
+            uv: from.uv.lerp(to.uv, 0.5),
+            color: from.color.lerp_to_gamma(to.color, 0.5),
+        };
+        let (ab, bc, ca) = (mid(a, b), mid(b, c), mid(c, a));
+        for triangle in [[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]] {
+            split(out, triangle, max_edge, depth + 1);
+        }
+    }
+
+    let mut out = Mesh::with_texture(mesh.texture_id);
+    for triangle in mesh.indices.chunks_exact(3) {
+        let corners = [0, 1, 2].map(|k| mesh.vertices[triangle[k] as usize]);
+        split(&mut out, corners, max_edge, 0);
+    }
+    out
+}
+
+/// Converts a position on the screen into a Typst point.
+fn to_point(pos: Pos2) -> Point {
+    Point::new(Abs::pt(pos.x as f64), Abs::pt(pos.y as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use typst_library::foundations::Smart;
+    use typst_library::layout::{Angle, Axes, FrameItem, FrameKind, GroupItem, Ratio};
+    use typst_library::visualize::{
+        ColorSpace, ConicGradient, Curve, Geometry, LinearGradient, Luma, Oklch, RadialGradient,
+    };
+    use typst_syntax::Span;
+
+    use super::*;
+    use crate::gui::testing::{assert_similar, export, preview};
+
+    /// How far the channels of a pixel may be off. Gradients are
+    /// interpolated linearly between their samples in the preview.
+    const TOLERANCE: u8 = 12;
+
+    /// A frame with a rectangle and an ellipse next to each other for each
+    /// paint. Rectangles are drawn by egui directly if they are solid, while
+    /// the ellipses are tessellated and colored by `paint_mesh`.
+    fn swatches(paints: &[Paint]) -> Frame {
+        let size = Size::new(Abs::pt(40.0), Abs::pt(30.0));
+        let mut frame = Frame::soft(Size::new(
+            Abs::pt(100.0),
+            Abs::pt(40.0 * paints.len() as f64 + 10.0),
+        ));
+        for (i, paint) in paints.iter().enumerate() {
+            let y = Abs::pt(40.0 * i as f64 + 10.0);
+            let rect = Geometry::Rect(size).filled(paint.clone());
+            let ellipse = Geometry::Curve(Curve::ellipse(size)).filled(paint.clone());
+            frame.push(
+                Point::new(Abs::pt(5.0), y),
+                FrameItem::Shape(rect, Span::detached()),
+            );
+            frame.push(
+                Point::new(Abs::pt(55.0), y),
+                FrameItem::Shape(ellipse, Span::detached()),
+            );
+        }
+        frame
+    }
+
+    fn stops() -> Vec<(Color, Ratio)> {
+        vec![
+            (Color::BLUE, Ratio::zero()),
+            (Color::YELLOW, Ratio::new(0.4)),
+            (Color::RED, Ratio::one()),
+        ]
+    }
+
+    fn linear(angle: f64, space: ColorSpace) -> Paint {
+        Paint::Gradient(Gradient::Linear(Arc::new(LinearGradient {
+            stops: stops(),
+            angle: Angle::deg(angle),
+            space,
+            relative: Smart::Auto,
+            anti_alias: true,
+        })))
+    }
+
+    #[test]
+    fn test_solid_colors_match_export() {
+        let frame = swatches(&[
+            Paint::Solid(Color::from_u8(200, 30, 90, 255)),
+            Paint::Solid(Color::Luma(Luma::new(0.3, 1.0))),
+            Paint::Solid(Color::Oklch(Oklch::new(0.7, 0.15, 140.0, 1.0))),
+            Paint::Solid(Color::EASTERN),
+        ]);
+        assert_similar(&preview(&frame), &export(&frame), 2);
+    }
+
+    #[test]
+    fn test_translucent_colors_match_export() {
+        let frame = swatches(&[
+            Paint::Solid(Color::from_u8(0, 0, 255, 128)),
+            Paint::Solid(Color::Luma(Luma::new(0.0, 0.25))),
+        ]);
+        assert_similar(&preview(&frame), &export(&frame), 2);
+    }
+
+    #[test]
+    fn test_linear_gradients_match_export() {
+        let frame = swatches(&[
+            linear(0.0, ColorSpace::Oklab),
+            linear(90.0, ColorSpace::Srgb),
+            linear(30.0, ColorSpace::Oklch),
+            linear(225.0, ColorSpace::Oklab),
+        ]);
+        assert_similar(&preview(&frame), &export(&frame), TOLERANCE);
+    }
+
+    #[test]
+    fn test_radial_and_conic_gradients_match_export() {
+        let center = Axes::splat(Ratio::new(0.5));
+        let radial = RadialGradient {
+            stops: stops(),
+            center,
+            radius: Ratio::new(0.5),
+            focal_center: Axes::new(Ratio::new(0.4), Ratio::new(0.4)),
+            focal_radius: Ratio::new(0.1),
+            space: ColorSpace::Oklab,
+            relative: Smart::Auto,
+            anti_alias: true,
+        };
+        let conic = ConicGradient {
+            stops: stops(),
+            angle: Angle::deg(45.0),
+            center,
+            space: ColorSpace::Oklab,
+            relative: Smart::Auto,
+            anti_alias: true,
+        };
+        let frame = swatches(&[
+            Paint::Gradient(Gradient::Radial(Arc::new(radial))),
+            Paint::Gradient(Gradient::Conic(Arc::new(conic))),
+        ]);
+        assert_similar(&preview(&frame), &export(&frame), TOLERANCE);
+    }
+
+    #[test]
+    fn test_gradients_relative_to_parent_match_export() {
+        // The gradient spans the hard frame of the group, not the shapes.
+        let Paint::Gradient(gradient) = linear(0.0, ColorSpace::Oklab) else {
+            unreachable!()
+        };
+        let paint = Paint::Gradient(gradient.with_relative(RelativeTo::Parent));
+        let inner = swatches(&[paint.clone(), paint]);
+        let mut frame = Frame::soft(inner.size() + Size::splat(Abs::pt(20.0)));
+        let group = GroupItem::new(inner.with_kind(FrameKind::Hard));
+        frame.push(Point::splat(Abs::pt(10.0)), FrameItem::Group(group));
+        assert_similar(&preview(&frame), &export(&frame), TOLERANCE);
+    }
+}
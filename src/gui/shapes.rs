@@ -1,6 +1,16 @@
-use eframe::egui::{Color32, CornerRadius, Pos2, Rect, Stroke, StrokeKind, Ui};
+use eframe::egui::{Color32, CornerRadius, Mesh, Pos2, Rect, Stroke, StrokeKind, Ui};
+use eframe::epaint::{Vertex, WHITE_UV};
+use lyon_tessellation::math::point;
+use lyon_tessellation::{
+    self as lyon, BuffersBuilder, FillOptions, FillTessellator, FillVertex, VertexBuffers,
+};
+use tiny_skia::{self as sk, PathSegment};
 
-use typst_library::visualize::Color;
+use typst_library::visualize::FillRule;
+
+/// The maximum distance between a curve and the polygon approximating it, in
+/// logical pixels.
+pub(super) const TOLERANCE: f32 = 0.1;
 
 pub trait Shapes {
     fn draw_rectangle_lines(
@@ -13,7 +23,7 @@ pub trait Shapes {
         color: Color32,
     );
     fn draw_rectangle(&mut self, x: f64, y: f64, w: f64, h: f64, color: Color32);
-    fn draw_line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, color: Color);
+    fn draw_line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, color: Color32);
 }
 
 impl Shapes for Ui {
@@ -63,22 +73,85 @@ impl Shapes for Ui {
             max: max_pos,
         };
 
-        let color = color;
-
         self.painter()
             .rect_filled(rect, CornerRadius::default(), color);
     }
-    fn draw_line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, color: Color) {
+    fn draw_line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, color: Color32) {
         let coord = [Pos2::new(x1 as _, y1 as _), Pos2::new(x2 as _, y2 as _)];
-        let rgb = color.to_rgb();
         let stroke = Stroke {
             width: thickness as _,
-            color: Color32::from_rgb(
-                (rgb.red * 256.0) as u8,
-                (rgb.green * 256.0) as u8,
-                (rgb.blue * 256.0) as u8,
-            ),
+            color,
         };
         self.painter().line_segment(coord, stroke);
     }
 }
+
+/// Turn the area covered by a path into a mesh of triangles with the given
+/// color, approximating curves up to the tolerance.
+pub(super) fn tessellate(
+    path: &sk::Path,
+    rule: FillRule,
+    color: Color32,
+    tolerance: f32,
+) -> Option<Mesh> {
+    let rule = match rule {
+        FillRule::NonZero => lyon::FillRule::NonZero,
+        FillRule::EvenOdd => lyon::FillRule::EvenOdd,
+    };
+    let options = FillOptions::tolerance(tolerance).with_fill_rule(rule);
+
+    let mut buffers: VertexBuffers<Vertex, u32> = VertexBuffers::new();
+    FillTessellator::new()
+        .tessellate_path(
+            &to_lyon_path(path),
+            &options,
+            &mut BuffersBuilder::new(&mut buffers, |vertex: FillVertex| Vertex {
+                pos: Pos2::new(vertex.position().x, vertex.position().y),
+                uv: WHITE_UV,
+                color,
+            }),
+        )
+        .ok()?;
+
+    Some(Mesh {
+        indices: buffers.indices,
+        vertices: buffers.vertices,
+        ..Mesh::default()
+    })
+}
+
+/// Convert a tiny-skia path into a lyon path.
+pub(super) fn to_lyon_path(path: &sk::Path) -> lyon::path::Path {
+    let mut builder = lyon::path::Path::builder();
+    let mut open = false;
+    for segment in path.segments() {
+        match segment {
+            PathSegment::MoveTo(p) => {
+                if open {
+                    builder.end(false);
+                }
+                builder.begin(point(p.x, p.y));
+                open = true;
+            }
+            PathSegment::LineTo(p) => {
+                builder.line_to(point(p.x, p.y));
+            }
+            PathSegment::QuadTo(p1, p2) => {
+                builder.quadratic_bezier_to(point(p1.x, p1.y), point(p2.x, p2.y));
+            }
+            PathSegment::CubicTo(p1, p2, p3) => {
+                builder.cubic_bezier_to(point(p1.x, p1.y), point(p2.x, p2.y), point(p3.x, p3.y));
+            }
+            PathSegment::Close => {
+                if open {
+                    builder.end(true);
+                }
+                open = false;
+            }
+        }
+    }
+    if open {
+        builder.end(false);
+    }
+    builder.build()
+}
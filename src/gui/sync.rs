@@ -0,0 +1,369 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use ttf_parser::GlyphId;
+use typst_library::layout::FrameItem::{Group, Image, Shape, Text};
+use typst_library::layout::{Abs, Frame, PagedDocument, Point, Transform};
+use typst_library::text::{Glyph, TextItem};
+use typst_library::visualize::FillRule;
+use typst_syntax::{LinkedNode, Side, Source, Span, SyntaxNode};
+
+/// An axis-aligned box on a page of the preview, in points.
+#[derive(Copy, Clone)]
+pub(super) struct Region {
+    /// The index of the page in the shown document.
+    pub page: usize,
+    /// The top left corner of the box.
+    pub min: Point,
+    /// The bottom right corner of the box.
+    pub max: Point,
+}
+
+impl Region {
+    /// The box on a page that covers the box between two corners of an item,
+    /// mapped onto the page with the transform.
+    pub fn new(page: usize, ts: Transform, min: Point, max: Point) -> Self {
+        let corners = [min, Point::new(max.x, min.y), max, Point::new(min.x, max.y)]
+            .map(|corner| corner.transform(ts));
+        let (mut min, mut max) = (corners[0], corners[0]);
+        for corner in &corners[1..] {
+            min = min.min(*corner);
+            max = max.max(*corner);
+        }
+        Self { page, min, max }
+    }
+}
+
+/// Find the byte offset in the source that produced the item at a point on a
+/// page, like SyncTeX does for LaTeX.
+///
+/// Glyphs lead to the exact character they were shaped from, while shapes and
+/// images lead to the start of the element that created them. The topmost
+/// item that was produced by the source wins, skipping items from other files,
+/// such as the glyphs of an imported one. Like when the frame is rendered,
+/// groups are transformed and items that they clip away can't be clicked.
+pub(super) fn jump_from_click(source: &Source, frame: &Frame, click: Point) -> Option<usize> {
+    let offset = |span: Span, offset: u16| {
+        let range = source.range(span)?;
+        Some((range.start + usize::from(offset)).min(range.end))
+    };
+
+    for (pos, item) in frame.items().rev() {
+        match item {
+            Group(group) => {
+                let ts = Transform::translate(pos.x, pos.y).pre_concat(group.transform);
+                let Some(inverse) = ts.invert() else { continue };
+                let click = click.transform(inverse);
+                if group
+                    .clip
+                    .as_ref()
+                    .is_some_and(|clip| !clip.contains(FillRule::NonZero, click))
+                {
+                    continue;
+                }
+                if let Some(found) = jump_from_click(source, &group.frame, click) {
+                    return Some(found);
+                }
+            }
+            Text(text) => {
+                for (glyph, min, max) in glyph_boxes(text) {
+                    if contains(*pos + min, *pos + max, click)
+                        && let Some(found) = offset(glyph.span.0, glyph.span.1)
+                    {
+                        return Some(found);
+                    }
+                }
+            }
+            Shape(shape, span) => {
+                let max = *pos + shape.geometry.bbox_size().to_point();
+                if contains(*pos, max, click)
+                    && let Some(found) = offset(*span, 0)
+                {
+                    return Some(found);
+                }
+            }
+            Image(_, size, span) => {
+                if contains(*pos, *pos + size.to_point(), click)
+                    && let Some(found) = offset(*span, 0)
+                {
+                    return Some(found);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The boxes of the items of a document, by the span that produced them, so
+/// that the items of a syntax node can be found without going through the
+/// whole document.
+#[derive(Default)]
+pub(super) struct SpanIndex(HashMap<Span, Vec<Entry>>);
+
+/// The box of an item, or of consecutive glyphs of a text run, that was
+/// produced by one span.
+struct Entry {
+    region: Region,
+    /// The number of the item in the whole document, in the order of the
+    /// pages and the items on them.
+    item: usize,
+    /// The glyphs of a text run within the box. Empty for other items.
+    glyphs: Range<usize>,
+}
+
+impl SpanIndex {
+    /// Index the items on the pages of a document.
+    pub fn new(document: &PagedDocument) -> Self {
+        let mut index = Self::default();
+        let mut item = 0;
+        for (page, frame) in document.pages.iter().enumerate() {
+            index.insert_frame(&frame.frame, Transform::identity(), page, &mut item);
+        }
+        index
+    }
+
+    /// Index the items of a frame, mapping them onto the page with the
+    /// transform.
+    fn insert_frame(&mut self, frame: &Frame, ts: Transform, page: usize, item: &mut usize) {
+        for (pos, frame_item) in frame.items() {
+            let ts = ts.pre_concat(Transform::translate(pos.x, pos.y));
+            *item += 1;
+            match frame_item {
+                Group(group) => {
+                    let ts = ts.pre_concat(group.transform);
+                    self.insert_frame(&group.frame, ts, page, item);
+                }
+                Text(text) => {
+                    // Consecutive glyphs from the same span share a box.
+                    let mut run: Option<(Span, Range<usize>, Point, Point)> = None;
+                    for (i, (glyph, min, max)) in glyph_boxes(text).enumerate() {
+                        match &mut run {
+                            Some((span, glyphs, run_min, run_max)) if *span == glyph.span.0 => {
+                                glyphs.end = i + 1;
+                                *run_min = run_min.min(min);
+                                *run_max = run_max.max(max);
+                            }
+                            _ => {
+                                if let Some((span, glyphs, min, max)) = run.take() {
+                                    self.insert(
+                                        span,
+                                        Region::new(page, ts, min, max),
+                                        *item,
+                                        glyphs,
+                                    );
+                                }
+                                run = Some((glyph.span.0, i..i + 1, min, max));
+                            }
+                        }
+                    }
+                    if let Some((span, glyphs, min, max)) = run {
+                        self.insert(span, Region::new(page, ts, min, max), *item, glyphs);
+                    }
+                }
+                Shape(shape, span) => {
+                    let max = shape.geometry.bbox_size().to_point();
+                    self.insert(
+                        *span,
+                        Region::new(page, ts, Point::zero(), max),
+                        *item,
+                        0..0,
+                    );
+                }
+                Image(_, size, span) => {
+                    let region = Region::new(page, ts, Point::zero(), size.to_point());
+                    self.insert(*span, region, *item, 0..0);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Add the box of an item, or of some of its glyphs, that the span
+    /// produced.
+    fn insert(&mut self, span: Span, region: Region, item: usize, glyphs: Range<usize>) {
+        self.0.entry(span).or_default().push(Entry {
+            region,
+            item,
+            glyphs,
+        });
+    }
+
+    /// The boxes of the items with one of the spans, in the order of the
+    /// document. The glyphs of a text run that follow each other share one
+    /// box, so that there is a box per line.
+    fn find(&self, spans: &HashSet<Span>) -> Vec<Region> {
+        let mut entries: Vec<&Entry> = spans
+            .iter()
+            .filter_map(|span| self.0.get(span))
+            .flatten()
+            .collect();
+        entries.sort_by_key(|entry| (entry.item, entry.glyphs.start));
+
+        let mut regions: Vec<Region> = vec![];
+        let mut last: Option<&Entry> = None;
+        for entry in entries {
+            match (last, regions.last_mut()) {
+                (Some(previous), Some(region))
+                    if previous.item == entry.item
+                        && !entry.glyphs.is_empty()
+                        && previous.glyphs.end == entry.glyphs.start =>
+                {
+                    region.min = region.min.min(entry.region.min);
+                    region.max = region.max.max(entry.region.max);
+                }
+                _ => regions.push(entry.region),
+            }
+            last = Some(entry);
+        }
+        regions
+    }
+}
+
+/// Find the boxes of the items in the document that were produced by the
+/// source at a byte offset.
+///
+/// If nothing was produced by the syntax node at the offset itself, such as
+/// for the name of a function, the items produced anywhere within the closest
+/// enclosing node that produced any are found instead.
+pub(super) fn jump_from_cursor(index: &SpanIndex, source: &Source, cursor: usize) -> Vec<Region> {
+    let root = LinkedNode::new(source.root());
+    let mut node = root
+        .leaf_at(cursor, Side::After)
+        .or_else(|| root.leaf_at(cursor, Side::Before));
+
+    // The whole document is produced by the root, so stop before it.
+    while let Some(current) = node.filter(|node| node.parent().is_some()) {
+        let mut spans = HashSet::new();
+        collect_spans(current.get(), &mut spans);
+
+        let regions = index.find(&spans);
+        if !regions.is_empty() {
+            return regions;
+        }
+        node = current.parent().cloned();
+    }
+
+    vec![]
+}
+
+/// Collect the spans of a syntax node and all of its descendants.
+fn collect_spans(node: &SyntaxNode, spans: &mut HashSet<Span>) {
+    spans.insert(node.span());
+    for child in node.children() {
+        collect_spans(child, spans);
+    }
+}
+
+/// The glyphs of a text run along with the corners of their boxes, relative
+/// to the start of the run's baseline.
+///
+/// A box spans the glyph's advance from where it was shaped and the font's
+/// ascender and descender, extended to the glyph's outline, such that it
+/// covers both the glyph and the space that belongs to it.
+fn glyph_boxes(text: &TextItem) -> impl Iterator<Item = (&Glyph, Point, Point)> {
+    let metrics = text.font.metrics();
+    let ascender = metrics.ascender.at(text.size);
+    let descender = metrics.descender.at(text.size);
+    let units = |value: i16| text.font.to_em(value).at(text.size);
+
+    let (mut x, mut y) = (Abs::zero(), Abs::zero());
+    text.glyphs.iter().map(move |glyph| {
+        let left = x + glyph.x_offset.at(text.size);
+        let baseline = -(y + glyph.y_offset.at(text.size));
+        let advance = glyph.x_advance.at(text.size);
+        let mut min = Point::new(left, baseline - ascender);
+        let mut max = Point::new(left + advance, baseline - descender);
+        if let Some(bbox) = text.font.ttf().glyph_bounding_box(GlyphId(glyph.id)) {
+            min = min.min(Point::new(
+                left + units(bbox.x_min),
+                baseline - units(bbox.y_max),
+            ));
+            max = max.max(Point::new(
+                left + units(bbox.x_max),
+                baseline - units(bbox.y_min),
+            ));
+        }
+
+        x += advance;
+        y += glyph.y_advance.at(text.size);
+        (glyph, min, max)
+    })
+}
+
+/// Whether a point is within the box spanned by two corners, which may be in
+/// any order.
+fn contains(a: Point, b: Point, point: Point) -> bool {
+    let (min, max) = (a.min(b), a.max(b));
+    (min.x..=max.x).contains(&point.x) && (min.y..=max.y).contains(&point.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use typst_library::World as _;
+
+    use super::*;
+    use crate::tests::renderer;
+
+    /// Compile a document and index its items.
+    fn compile(text: &str) -> (PagedDocument, Source, SpanIndex) {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("main.typ");
+        std::fs::write(&input, text).unwrap();
+        let mut renderer = renderer(&input);
+        let document = renderer.render_from_path(&input, None).output.unwrap();
+        let source = renderer.world.source(renderer.world.main()).unwrap();
+        let index = SpanIndex::new(&document);
+        (document, source, index)
+    }
+
+    /// The boxes of the items produced by the source at the text's first
+    /// occurrence.
+    fn find(source: &Source, index: &SpanIndex, text: &str) -> Vec<Region> {
+        jump_from_cursor(index, source, source.text().find(text).unwrap() + 1)
+    }
+
+    #[test]
+    fn test_jump_from_cursor_to_wrapped_text() {
+        // The name of the function produced nothing, so the whole call is
+        // highlighted, with a box per line.
+        let (_, source, index) = compile("#block(width: 80pt)[aaaa bbbb cccc dddd eeee ffff]");
+        let regions = find(&source, &index, "block");
+        assert!(regions.len() >= 2, "the text is on several lines");
+        for pair in regions.windows(2) {
+            assert!(pair[0].max.y <= pair[1].min.y + Abs::pt(1.0));
+        }
+    }
+
+    #[test]
+    fn test_jump_from_click_below_the_baseline() {
+        let (document, source, index) = compile("#text(size: 20pt)[gyp]");
+        let [region] = find(&source, &index, "gyp")[..] else {
+            panic!("the text is on one line");
+        };
+        let click = Point::new(region.min.x + Abs::pt(3.0), region.max.y - Abs::pt(1.0));
+        let frame = &document.pages[0].frame;
+        assert_eq!(
+            jump_from_click(&source, frame, click),
+            source.text().find("gyp")
+        );
+    }
+
+    #[test]
+    fn test_jump_from_click_ignores_clipped_text() {
+        let (document, source, index) =
+            compile("#box(clip: true, width: 12pt)[#text(size: 20pt)[Hello]]");
+        let [region] = find(&source, &index, "Hello")[..] else {
+            panic!("the text is on one line");
+        };
+        let y = (region.min.y + region.max.y) / 2.0;
+        let frame = &document.pages[0].frame;
+        let inside = Point::new(region.min.x + Abs::pt(4.0), y);
+        assert_eq!(
+            jump_from_click(&source, frame, inside),
+            source.text().find("Hello")
+        );
+        let outside = Point::new(region.max.x - Abs::pt(4.0), y);
+        assert_eq!(jump_from_click(&source, frame, outside), None);
+    }
+}
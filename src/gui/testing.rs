@@ -0,0 +1,210 @@
+//! Helpers for tests that compare the preview with the images that
+//! typst-render exports.
+
+use eframe::egui::{self, ClippedPrimitive, Context, Mesh, Pos2, Rect, pos2, vec2};
+use eframe::epaint::Primitive;
+use typst_library::foundations::{Content, Smart};
+use typst_library::layout::{Frame, Page, Transform};
+
+use super::update::{State, render_frame};
+
+/// How many samples are taken per pixel along each axis of the preview.
+const SAMPLES: usize = 4;
+
+/// An image with premultiplied RGBA pixels, in rows from top to bottom.
+pub struct Pixels {
+    width: usize,
+    height: usize,
+    data: Vec<[u8; 4]>,
+}
+
+impl Pixels {
+    /// The pixel in the column and row.
+    pub fn get(&self, x: usize, y: usize) -> [u8; 4] {
+        self.data[y * self.width + x]
+    }
+}
+
+/// Render a frame on a white page with typst-render, one pixel per point.
+/// It is rendered with `SAMPLES` times the resolution and scaled down, like
+/// the preview, because typst-render samples gradients at the corners of
+/// pixels instead of at their centers.
+pub fn export(frame: &Frame) -> Pixels {
+    let page = Page {
+        frame: frame.clone(),
+        fill: Smart::Auto,
+        numbering: None,
+        supplement: Content::empty(),
+        number: 1,
+    };
+    let pixmap = typst_render::render(&page, SAMPLES as f32);
+    downsample(&Pixels {
+        width: pixmap.width() as usize,
+        height: pixmap.height() as usize,
+        data: pixmap
+            .data()
+            .chunks_exact(4)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+            .collect(),
+    })
+}
+
+/// Render a frame on a white page like the preview does at a zoom of 100%,
+/// one pixel per point.
+pub fn preview(frame: &Frame) -> Pixels {
+    let size = frame.size();
+    let (width, height) = (
+        size.x.to_pt().round() as usize,
+        size.y.to_pt().round() as usize,
+    );
+    let ctx = Context::default();
+    let input = egui::RawInput {
+        screen_rect: Some(Rect::from_min_size(
+            Pos2::ZERO,
+            vec2(width as f32, height as f32),
+        )),
+        ..Default::default()
+    };
+    let output = ctx.run(input, |ctx| {
+        egui::CentralPanel::default()
+            .frame(egui::Frame::NONE)
+            .show(ctx, |ui| {
+                render_frame(ui, frame, State::new(size, Transform::identity()), false);
+            });
+    });
+
+    let mut samples = Pixels {
+        width: width * SAMPLES,
+        height: height * SAMPLES,
+        data: vec![[255; 4]; width * height * SAMPLES * SAMPLES],
+    };
+    for ClippedPrimitive {
+        clip_rect,
+        primitive,
+    } in ctx.tessellate(output.shapes, output.pixels_per_point)
+    {
+        if let Primitive::Mesh(mesh) = primitive {
+            rasterize(&mut samples, &mesh, clip_rect);
+        }
+    }
+
+    downsample(&samples)
+}
+
+/// Anti-alias an image with `SAMPLES` times the resolution by averaging the
+/// samples within each pixel.
+fn downsample(samples: &Pixels) -> Pixels {
+    let (width, height) = (samples.width / SAMPLES, samples.height / SAMPLES);
+    let data = (0..width * height)
+        .map(|index| {
+            let (x, y) = (index % width * SAMPLES, index / width * SAMPLES);
+            let mut sum = [0; 4];
+            for v in y..y + SAMPLES {
+                for u in x..x + SAMPLES {
+                    for (total, channel) in sum.iter_mut().zip(samples.get(u, v)) {
+                        *total += channel as usize;
+                    }
+                }
+            }
+            sum.map(|total| (total as f32 / (SAMPLES * SAMPLES) as f32).round() as u8)
+        })
+        .collect();
+    Pixels {
+        width,
+        height,
+        data,
+    }
+}
+
+/// Draw the triangles of a mesh into an image with `SAMPLES` times the
+/// resolution, blending the interpolated vertex colors over the samples whose
+/// centers they cover. Textures are ignored, so only meshes with vertex colors
+/// are drawn correctly.
+fn rasterize(samples: &mut Pixels, mesh: &Mesh, clip: Rect) {
+    let cross = |a: Pos2, b: Pos2, p: Pos2| (b - a).x * (p - a).y - (b - a).y * (p - a).x;
+    let scale = SAMPLES as f32;
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| mesh.vertices[triangle[k] as usize]);
+        let area = cross(a.pos, b.pos, c.pos);
+        if area == 0.0 {
+            continue;
+        }
+
+        let bounds = Rect::from_points(&[a.pos, b.pos, c.pos]).intersect(clip);
+        let range = |min: f32, max: f32, len: usize| {
+            (min * scale).floor().max(0.0) as usize
+                ..((max * scale).ceil().max(0.0) as usize).min(len)
+        };
+        for y in range(bounds.min.y, bounds.max.y, samples.height) {
+            for x in range(bounds.min.x, bounds.max.x, samples.width) {
+                // Samples are taken slightly off their centers, so that none
+                // is on the edge shared by two triangles and drawn twice.
+                let p = pos2(x as f32 + 0.5 + 1e-3, y as f32 + 0.5 + 2e-3) / scale;
+                let weights = [
+                    cross(b.pos, c.pos, p) / area,
+                    cross(c.pos, a.pos, p) / area,
+                    cross(a.pos, b.pos, p) / area,
+                ];
+                if !clip.contains(p) || weights.iter().any(|&w| w <= 0.0) {
+                    continue;
+                }
+
+                let colors = [a.color, b.color, c.color].map(|color| color.to_array());
+                let source: [f32; 4] =
+                    std::array::from_fn(|i| (0..3).map(|k| weights[k] * colors[k][i] as f32).sum());
+                let sample = &mut samples.data[y * samples.width + x];
+                let keep = 1.0 - source[3] / 255.0;
+                *sample = std::array::from_fn(|i| {
+                    (source[i] + sample[i] as f32 * keep)
+                        .round()
+                        .clamp(0.0, 255.0) as u8
+                });
+            }
+        }
+    }
+}
+
+/// Check that every channel of every pixel of the preview is within the
+/// tolerance of the range of that channel in the exported image around the
+/// pixel, at most one pixel away. The distance allows for anti-aliasing along
+/// edges, which differs between the two.
+#[track_caller]
+pub fn assert_similar(preview: &Pixels, export: &Pixels, tolerance: u8) {
+    assert_eq!(
+        (preview.width, preview.height),
+        (export.width, export.height),
+        "the images differ in size"
+    );
+
+    let mut mismatches = vec![];
+    for y in 0..preview.height {
+        for x in 0..preview.width {
+            let pixel = preview.get(x, y);
+            let mut range = [(u8::MAX, u8::MIN); 4];
+            for v in y.saturating_sub(1)..(y + 2).min(export.height) {
+                for u in x.saturating_sub(1)..(x + 2).min(export.width) {
+                    for (i, (min, max)) in range.iter_mut().enumerate() {
+                        *min = (*min).min(export.get(u, v)[i]);
+                        *max = (*max).max(export.get(u, v)[i]);
+                    }
+                }
+            }
+            let matched = range.iter().zip(pixel).all(|(&(min, max), channel)| {
+                min.saturating_sub(tolerance) <= channel && channel <= max.saturating_add(tolerance)
+            });
+            if !matched {
+                mismatches.push((x, y));
+            }
+        }
+    }
+
+    if let Some(&(x, y)) = mismatches.first() {
+        panic!(
+            "{} pixels differ, the first at ({x}, {y}) is {:?} in the preview and {:?} \
+             in the export",
+            mismatches.len(),
+            preview.get(x, y),
+            export.get(x, y),
+        );
+    }
+}
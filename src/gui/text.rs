@@ -1,21 +1,206 @@
-use eframe::egui::{Color32, Pos2, Ui, Align2, FontFamily, FontId};
+use std::collections::HashMap;
+use std::sync::Arc;
 
-pub trait Text {
-    fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, font_family: FontFamily, color: Color32);
+use eframe::egui::{Color32, Id, Mesh, Pos2, Ui};
+use tiny_skia as sk;
+use ttf_parser::{GlyphId, OutlineBuilder};
+use typst_library::layout::{Abs, Point, Ratio, Size, Transform};
+use typst_library::text::color::{glyph_frame, should_outline};
+use typst_library::text::{Font, TextItem};
+use typst_library::visualize::FillRule;
+
+use super::clip::clip_mesh;
+use super::paint::{Placement, fill_path, paint_mesh};
+use super::shapes::{TOLERANCE, tessellate};
+use super::update::{State, render_frame, scale, stroke_outline, to_sk_transform};
+
+/// How many tessellations of a glyph there are per doubling of its size on
+/// the screen. A glyph is only tessellated again when the zoom crosses one of
+/// these steps.
+const STEPS_PER_OCTAVE: f32 = 2.0;
+
+/// How many tessellated outlines are kept at most. Beyond that, the ones
+/// that were not drawn in the current pass are dropped.
+const MAX_OUTLINES: usize = 4096;
+
+/// A glyph at a size step: the hash of the font, the glyph id and the step.
+type OutlineKey = (u128, u16, i32);
+
+/// The tessellated outlines of the glyphs drawn so far, in font units, along
+/// with the pass they were last drawn in. Glyphs without an outline map to
+/// `None`.
+#[derive(Clone, Default)]
+struct Outlines(HashMap<OutlineKey, (Option<Arc<Mesh>>, u64)>);
+
+/// Render a text run, mapping its points to the screen with the state.
+///
+/// Each glyph is painted at the position Typst shaped it at, so that the
+/// text is laid out exactly as in the exported documents.
+pub(super) fn render_text(ui: &mut Ui, text: &TextItem, state: State, display: bool) {
+    let ts = state.ts;
+    if display {
+        let point = Point::zero().transform(ts);
+        tracing::trace!(
+            x = point.x.to_pt(),
+            y = point.y.to_pt(),
+            size = text.size.to_pt() * scale(ts),
+            font = %text.font.info().family,
+            text = %text.text,
+            "drawing text",
+        );
+    }
+
+    let font_hash = typst_utils::hash128(&text.font);
+    let font_scale = Ratio::new(text.size / Abs::pt(text.font.units_per_em()));
+
+    // The outlines are taken out of the ui's memory for the whole run, so
+    // that it is only locked twice per run rather than per glyph.
+    let ctx = ui.ctx().clone();
+    let cache = Id::new("typst-glyph-outlines");
+    let pass = ctx.cumulative_pass_nr();
+    let mut outlines =
+        ctx.data_mut(|data| std::mem::take(data.get_temp_mut_or_default::<Outlines>(cache)));
+
+    let mut mesh = Mesh::default();
+    let mut x = Abs::zero();
+    let mut y = Abs::zero();
+    for glyph in &text.glyphs {
+        let x_offset = x + glyph.x_offset.at(text.size);
+        let y_offset = y + glyph.y_offset.at(text.size);
+
+        if should_outline(&text.font, glyph) {
+            // Flip vertically because font design coordinates are y-up.
+            let local = Transform::translate(x_offset, -y_offset)
+                .pre_concat(Transform::scale(font_scale, -font_scale));
+            let glyph_ts = ts.pre_concat(local);
+
+            let key = (font_hash, glyph.id);
+            if let Some(outline) = outlines.get(&text.font, key, scale(glyph_ts) as f32, pass) {
+                append(&mut mesh, &outline, glyph_ts);
+            }
+
+            if let Some(stroke) = &text.stroke
+                && stroke.thickness > Abs::zero()
+                && let Some(path) = glyph_path(&text.font, glyph.id)
+                && let Some(path) = path.transform(to_sk_transform(local))
+                && let Some(outline) = stroke_outline(&path, stroke, scale(ts))
+                && let Some(outline) = outline.transform(to_sk_transform(ts))
+            {
+                let placement = Placement::new(Size::zero(), true);
+                fill_path(
+                    ui,
+                    &outline,
+                    FillRule::NonZero,
+                    &stroke.paint,
+                    state,
+                    placement,
+                );
+            }
+        } else {
+            // Color glyphs, such as emoji, are drawn like a small frame.
+            let glyph_ts = Transform::translate(x_offset, -y_offset - text.size)
+                .pre_concat(Transform::scale(font_scale, font_scale));
+            let (frame, _) = glyph_frame(&text.font, glyph.id);
+            render_frame(ui, &frame, state.pre_concat(glyph_ts), display);
+        }
+
+        x += glyph.x_advance.at(text.size);
+        y += glyph.y_advance.at(text.size);
+    }
+
+    outlines.evict(pass);
+    ctx.data_mut(|data| data.insert_temp(cache, outlines));
+
+    // Paints relative to the text itself span its baseline, like in the PNG
+    // export.
+    if !mesh.is_empty() {
+        let placement = Placement::new(Size::zero(), true);
+        let mesh = paint_mesh(ui.ctx(), mesh, &text.fill, state, placement);
+        ui.painter().add(clip_mesh(mesh, state.clips));
+    }
+}
+
+impl Outlines {
+    /// Get the tessellated outline of a glyph that is precise enough when one
+    /// font unit takes up the given number of logical pixels, tessellating it
+    /// if necessary.
+    fn get(
+        &mut self,
+        font: &Font,
+        (hash, id): (u128, u16),
+        px_per_unit: f32,
+        pass: u64,
+    ) -> Option<Arc<Mesh>> {
+        if !px_per_unit.is_finite() || px_per_unit <= 0.0 {
+            return None;
+        }
+
+        let step = (px_per_unit.log2() * STEPS_PER_OCTAVE).ceil() as i32;
+        let (outline, drawn) = self.0.entry((hash, id, step)).or_insert_with(|| {
+            let tolerance = TOLERANCE / (step as f32 / STEPS_PER_OCTAVE).exp2();
+            let outline = glyph_path(font, id)
+                .and_then(|path| tessellate(&path, FillRule::NonZero, Color32::WHITE, tolerance))
+                .map(Arc::new);
+            (outline, pass)
+        });
+        *drawn = pass;
+        outline.clone()
+    }
+
+    /// Drop the outlines that were not drawn in the current pass if there are
+    /// too many.
+    fn evict(&mut self, pass: u64) {
+        if self.0.len() > MAX_OUTLINES {
+            self.0.retain(|_, (_, drawn)| *drawn == pass);
+        }
+    }
+}
+
+/// Add a glyph's outline to a mesh, mapping it to the screen with the
+/// transform.
+fn append(mesh: &mut Mesh, outline: &Mesh, ts: Transform) {
+    let [sx, ky, kx, sy] = [ts.sx, ts.ky, ts.kx, ts.sy].map(|v| v.get() as f32);
+    let (tx, ty) = (ts.tx.to_pt() as f32, ts.ty.to_pt() as f32);
+
+    let start = mesh.vertices.len() as u32;
+    mesh.indices
+        .extend(outline.indices.iter().map(|index| start + index));
+    mesh.vertices.extend(outline.vertices.iter().map(|vertex| {
+        let Pos2 { x, y } = vertex.pos;
+        let mut vertex = *vertex;
+        vertex.pos = Pos2::new(sx * x + kx * y + tx, ky * x + sy * y + ty);
+        vertex
+    }));
+}
+
+/// The outline of a glyph as a path in font units.
+fn glyph_path(font: &Font, id: u16) -> Option<sk::Path> {
+    let mut builder = WrappedPathBuilder(sk::PathBuilder::new());
+    font.ttf().outline_glyph(GlyphId(id), &mut builder)?;
+    builder.0.finish()
 }
 
-impl Text for Ui {
-    fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, font_family: FontFamily, color: Color32) {
-        let pos = Pos2 { x, y };
+/// Collects a glyph's outline into a tiny-skia path.
+struct WrappedPathBuilder(sk::PathBuilder);
 
-        let anchor = Align2::LEFT_TOP;
+impl OutlineBuilder for WrappedPathBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to(x, y);
+    }
 
-        let text = text;
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to(x, y);
+    }
 
-        let font_id = FontId::new(font_size, font_family);
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.quad_to(x1, y1, x, y);
+    }
 
-        let text_color = color;
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.cubic_to(x1, y1, x2, y2, x, y);
+    }
 
-        self.painter().text(pos, anchor, text, font_id, text_color);
+    fn close(&mut self) {
+        self.0.close();
     }
 }
@@ -0,0 +1,281 @@
+use std::fmt::Write as _;
+
+use eframe::egui::{CollapsingHeader, Id, Label, Response, RichText, ScrollArea, Ui};
+use typst_library::foundations::Repr as _;
+use typst_library::introspection::Tag as TypstTag;
+use typst_library::layout::FrameItem::{Group, Image, Link, Shape, Tag, Text};
+use typst_library::layout::{Abs, Frame, FrameKind, PagedDocument, Point, Size, Transform};
+use typst_library::model::Destination;
+use typst_library::visualize::Geometry;
+
+use super::TreeNode;
+use super::pages::PageNumbers;
+use super::sync::Region;
+
+/// How many characters of a text run are shown in its label.
+const MAX_TEXT: usize = 32;
+
+/// An item of a frame as listed in the inspector.
+pub(super) struct Entry {
+    /// A one-line summary of the item, including its position within its
+    /// parent.
+    label: String,
+    /// Further properties of the item, such as its transform, one per line.
+    details: String,
+    /// The box the item covers on its page, if it is visible.
+    region: Option<Region>,
+}
+
+impl TreeNode {
+    /// Build the hierarchy of the items in the pages of a document, labeling
+    /// the pages with their numbers.
+    pub fn new(document: &PagedDocument, numbers: &PageNumbers) -> Self {
+        let pages = document
+            .pages
+            .iter()
+            .enumerate()
+            .map(|(index, page)| {
+                let size = page.frame.size();
+                let entry = Entry {
+                    label: format!("Page {} {}", numbers.number(index), size_repr(size)),
+                    details: format!("{} items", page.frame.items().len()),
+                    region: Some(Region::new(
+                        index,
+                        Transform::identity(),
+                        Point::zero(),
+                        size.to_point(),
+                    )),
+                };
+                let children = frame_nodes(&page.frame, Transform::identity(), index);
+                TreeNode::Node(entry, children)
+            })
+            .collect::<Vec<_>>();
+
+        let label = if pages.len() == numbers.total() {
+            format!("Document ({} pages)", pages.len())
+        } else {
+            format!("Document ({} of {} pages)", pages.len(), numbers.total())
+        };
+        let entry = Entry {
+            label,
+            details: String::new(),
+            region: None,
+        };
+        TreeNode::Node(entry, pages)
+    }
+
+    /// Show the hierarchy with collapsible groups. Returns the region of the
+    /// item the pointer is over, if any, and whether it was clicked.
+    pub fn show(&self, ui: &mut Ui) -> Option<(Region, bool)> {
+        let mut hovered = None;
+        ScrollArea::both()
+            .id_salt("typst-frame-tree")
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                self.show_node(ui, Id::new("typst-frame-tree"), 0, &mut hovered)
+            });
+        hovered
+    }
+
+    fn show_node(&self, ui: &mut Ui, id: Id, depth: usize, hovered: &mut Option<(Region, bool)>) {
+        let (entry, response) = match self {
+            TreeNode::Leaf(entry) => {
+                let label = Label::new(RichText::new(&entry.label).monospace()).truncate();
+                (entry, ui.add(label))
+            }
+            TreeNode::Node(entry, children) => {
+                // Only the pages are listed initially.
+                let response = CollapsingHeader::new(RichText::new(&entry.label).monospace())
+                    .id_salt(id)
+                    .default_open(depth == 0)
+                    .show(ui, |ui| {
+                        for (index, child) in children.iter().enumerate() {
+                            child.show_node(ui, id.with(index), depth + 1, hovered);
+                        }
+                    });
+                (entry, response.header_response)
+            }
+        };
+
+        let response: Response = if entry.details.is_empty() {
+            response
+        } else {
+            response.on_hover_text(RichText::new(&entry.details).monospace())
+        };
+        if let Some(region) = entry.region
+            && response.hovered()
+        {
+            *hovered = Some((region, response.clicked()));
+        }
+    }
+}
+
+/// Build the nodes for the items of a frame, whose points are mapped onto the
+/// page with the transform.
+fn frame_nodes(frame: &Frame, ts: Transform, page: usize) -> Vec<TreeNode> {
+    let mut nodes = vec![];
+    for (pos, item) in frame.items() {
+        let ts = ts.pre_concat(Transform::translate(pos.x, pos.y));
+        let at = point_repr(*pos);
+        let mut details = format!(
+            "position  {at}\non page   {}",
+            point_repr(Point::zero().transform(ts))
+        );
+
+        let node = match item {
+            Group(group) => {
+                let ts = ts.pre_concat(group.transform);
+                let size = group.frame.size();
+                let kind = match group.frame.kind() {
+                    FrameKind::Hard => "hard",
+                    FrameKind::Soft => "soft",
+                };
+                write!(
+                    details,
+                    "\nframe     {kind}, {} items",
+                    group.frame.items().len()
+                )
+                .ok();
+                if !group.transform.is_identity() {
+                    write!(details, "\ntransform {}", transform_repr(group.transform)).ok();
+                }
+                write!(details, "\nto page   {}", transform_repr(ts)).ok();
+                if group.clip.is_some() {
+                    details.push_str("\nclipped");
+                }
+                if let Some(label) = group.label {
+                    write!(details, "\nlabel     {}", label.repr()).ok();
+                }
+                let entry = Entry {
+                    label: format!("Group {} at {at}", size_repr(size)),
+                    details,
+                    region: Some(Region::new(page, ts, Point::zero(), size.to_point())),
+                };
+                TreeNode::Node(entry, frame_nodes(&group.frame, ts, page))
+            }
+            Text(text) => {
+                let mut content: String = text.text.chars().take(MAX_TEXT).collect();
+                if text.text.chars().count() > MAX_TEXT {
+                    content.push('…');
+                }
+                let family = &text.font.info().family;
+                let size = text.size.to_pt();
+                write!(details, "\nglyphs    {}", text.glyphs.len()).ok();
+                write!(details, "\nlanguage  {}", text.lang.as_str()).ok();
+                let width = text.width();
+                let metrics = text.font.metrics();
+                let top = -metrics.ascender.at(text.size);
+                let bottom = -metrics.descender.at(text.size);
+                let entry = Entry {
+                    label: format!("Text {family} {size:.1}pt {content:?} at {at}"),
+                    details,
+                    region: Some(Region::new(
+                        page,
+                        ts,
+                        Point::new(Abs::zero(), top),
+                        Point::new(width, bottom),
+                    )),
+                };
+                TreeNode::Leaf(entry)
+            }
+            Shape(shape, _) => {
+                let size = shape.geometry.bbox_size();
+                let kind = match shape.geometry {
+                    Geometry::Line(_) => "Line",
+                    Geometry::Rect(_) => "Rect",
+                    Geometry::Curve(_) => "Curve",
+                };
+                if let Some(fill) = &shape.fill {
+                    write!(details, "\nfill      {}", fill.repr()).ok();
+                }
+                if let Some(stroke) = &shape.stroke {
+                    let thickness = stroke.thickness.to_pt();
+                    write!(
+                        details,
+                        "\nstroke    {thickness:.2}pt {}",
+                        stroke.paint.repr()
+                    )
+                    .ok();
+                }
+                let entry = Entry {
+                    label: format!("Shape {kind} {} at {at}", size_repr(size)),
+                    details,
+                    region: Some(Region::new(page, ts, Point::zero(), size.to_point())),
+                };
+                TreeNode::Leaf(entry)
+            }
+            Image(image, size, _) => {
+                write!(details, "\nformat    {:?}", image.format()).ok();
+                write!(details, "\npixels    {}×{}", image.width(), image.height()).ok();
+                if let Some(alt) = image.alt() {
+                    write!(details, "\nalt       {alt:?}").ok();
+                }
+                let entry = Entry {
+                    label: format!("Image {} at {at}", size_repr(*size)),
+                    details,
+                    region: Some(Region::new(page, ts, Point::zero(), size.to_point())),
+                };
+                TreeNode::Leaf(entry)
+            }
+            Link(destination, size) => {
+                let target = match destination {
+                    Destination::Url(url) => url.as_str().to_owned(),
+                    Destination::Position(position) => {
+                        format!("page {} {}", position.page, point_repr(position.point))
+                    }
+                    Destination::Location(location) => format!("{location:?}"),
+                };
+                let entry = Entry {
+                    label: format!("Link {} at {at} to {target}", size_repr(*size)),
+                    details,
+                    region: Some(Region::new(page, ts, Point::zero(), size.to_point())),
+                };
+                TreeNode::Leaf(entry)
+            }
+            Tag(tag) => {
+                let label = match tag {
+                    TypstTag::Start(content, _) => {
+                        if let Some(location) = content.location() {
+                            write!(details, "\nlocation  {location:?}").ok();
+                        }
+                        format!("Tag start {} at {at}", content.elem().name())
+                    }
+                    TypstTag::End(location, _, _) => {
+                        write!(details, "\nlocation  {location:?}").ok();
+                        format!("Tag end at {at}")
+                    }
+                };
+                TreeNode::Leaf(Entry {
+                    label,
+                    details,
+                    region: None,
+                })
+            }
+        };
+        nodes.push(node);
+    }
+    nodes
+}
+
+/// Format a point in points, like `(12.00, 34.50)`.
+fn point_repr(point: Point) -> String {
+    format!("({:.2}, {:.2})", point.x.to_pt(), point.y.to_pt())
+}
+
+/// Format a size in points, like `120.00×40.00`.
+fn size_repr(size: Size) -> String {
+    format!("{:.2}×{:.2}", size.x.to_pt(), size.y.to_pt())
+}
+
+/// Format a transform as its matrix, like in CSS.
+fn transform_repr(ts: Transform) -> String {
+    format!(
+        "matrix({:.3}, {:.3}, {:.3}, {:.3}, {:.2}, {:.2})",
+        ts.sx.get(),
+        ts.ky.get(),
+        ts.kx.get(),
+        ts.sy.get(),
+        ts.tx.to_pt(),
+        ts.ty.to_pt(),
+    )
+}
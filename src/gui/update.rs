@@ -1,161 +1,393 @@
-use super::hash_u64;
+use super::clip::{Clip, GroupClip};
+use super::compiler::Job;
+use super::diagnostics;
+use super::image::render_image;
+use super::paint::{Placement, fill_path, to_color32};
 use super::shapes::Shapes as _;
-use super::text::Text as _;
-use super::{MyApp, collect_font_from_frame};
+use super::sync;
+use super::text::render_text;
+use super::{MyApp, TreeNode, View, register_cjk_fallback};
 use eframe::egui;
 use egui::DroppedFile;
 use egui::containers::Frame;
-use egui::{Color32, FontFamily, Ui};
-use typst_library::layout::FrameItem::{Group, Image, Shape, Text};
-use typst_library::layout::{Abs, Frame as TypstFrame, Point};
-use typst_library::text::TextItem;
-use typst_library::visualize::{Color, Geometry::Line, Paint::Solid, Shape as TypstShape};
+use egui::{Color32, Ui};
+use tiny_skia as sk;
+use typst_library::diag::Severity;
+use typst_library::layout::FrameItem::{Group, Image, Link, Shape, Tag, Text};
+use typst_library::layout::{Abs, Frame as TypstFrame, GroupItem, Point, Size, Transform};
+use typst_library::visualize::{
+    Curve, CurveItem, DashPattern, FillRule, FixedStroke, Geometry, LineCap, LineJoin, Paint,
+    Shape as TypstShape,
+};
 
 use std::io::Read;
 
-fn render_text(ui: &mut Ui, text: &TextItem, point: Point, display: bool) {
-    // if display {
-    //     if !text.glyphs.iter().any(|x| x.c.is_whitespace()) {
-    //         println!("render_text {:?}", point);
-    //         tracing::debug!("render_text {:?}", point);
-    //         for x in &text.glyphs {
-    //             print!("{:?},", x.c);
-    //             tracing::debug!("{:?},", x.c);
-    //         }
-    //         println!();
-    //     }
-    // }
-    //
-
-    if display {
-        super::print_font_info(text.font.ttf());
-    }
-
-    let font_hash = hash_u64(text.font.data().as_slice());
-    let font_name = format!("font-{}", font_hash);
-    let family = FontFamily::Name(font_name.clone().into());
-
-    let color = match text.fill {
-        Solid(color) => color,
-        _ => Color::BLACK,
-    };
-    let rgb_color = color.to_rgb();
-
-    let content = text.text.as_str();
-
-    if display {
-        println!(
-            "draw text at ({}, {}) font size {} font_name {} content {} color {:?}",
-            point.x.to_pt(),
-            point.y.to_pt(),
-            text.size.to_pt(),
-            &font_name,
-            &content,
-            &rgb_color
-        );
+/// How the items of a frame are mapped to the screen.
+#[derive(Copy, Clone)]
+pub(super) struct State<'a> {
+    /// Maps points of the current item to the screen.
+    pub ts: Transform,
+    /// Maps points of the first hard frame in the hierarchy to the screen.
+    /// Paints relative to the parent are laid out in this frame.
+    pub container_ts: Transform,
+    /// The size of the first hard frame in the hierarchy.
+    pub container_size: Size,
+    /// Areas on the screen that meshes are clipped to, in addition to the
+    /// ui's clip rect.
+    pub clips: &'a [Clip],
+}
+
+impl State<'_> {
+    /// Creates the state for a page of the given size that is mapped to the
+    /// screen with the transform.
+    pub fn new(size: Size, ts: Transform) -> Self {
+        Self {
+            ts,
+            container_ts: ts,
+            container_size: size,
+            clips: &[],
+        }
     }
 
-    ui.draw_text(
-        &content,
-        point.x.to_pt() as f32,
-        point.y.to_pt() as f32,
-        text.size.to_pt() as f32,
-        family,
-        Color32::from_rgb(
-            (rgb_color.red * 256.0) as _,
-            (rgb_color.green * 256.0) as _,
-            (rgb_color.blue * 256.0) as _,
-        ),
-    );
+    /// Pre-concatenate a transform to the current item's one.
+    pub fn pre_concat(self, ts: Transform) -> Self {
+        Self {
+            ts: self.ts.pre_concat(ts),
+            ..self
+        }
+    }
 }
 
-fn render_frame(
-    ui: &mut Ui,
-    frame: &TypstFrame,
-    offset: Point,
-    display: bool,
-    line_count: &mut u32,
-) {
-    // if display {
-    //     println!("render_frame");
-    // }
-    for (point, item) in frame.items() {
-        if display {
-            println!("one frame item");
-        }
-        let origin = *point + offset;
-        // if display {
-        //     println!("{:?} {:?}", origin, item);
-        //     tracing::debug!("#{:?} {:?}", point, item);
-        // }
-        match item {
-            Text(text) => render_text(ui, text, origin, display),
-            Group(group) => render_frame(ui, &group.frame, origin, display, line_count),
-            Shape(
-                TypstShape {
-                    geometry: Line(line_to),
-                    stroke: Some(stroke),
-                    ..
-                },
-                _,
-            ) => {
-                *line_count += 1;
-                if *line_count > 23 {
-                    return;
+/// Render a geometrical shape.
+fn render_shape(ui: &mut Ui, shape: &TypstShape, state: State) -> Option<()> {
+    let ts = state.ts;
+    let scale = scale(ts);
+    let stroke = shape
+        .stroke
+        .as_ref()
+        .filter(|stroke| stroke.thickness > Abs::zero());
+
+    // Solid rectangles that stay axis-aligned on the screen and plain lines
+    // can be drawn by egui directly, unless they must be clipped to a
+    // polygon.
+    let mut filled = false;
+    match shape.geometry {
+        _ if !state.clips.is_empty() => {}
+        Geometry::Rect(size) if ts.kx.is_zero() && ts.ky.is_zero() => {
+            let a = Point::zero().transform(ts);
+            let b = size.to_point().transform(ts);
+            let (x, y) = (a.x.min(b.x).to_pt(), a.y.min(b.y).to_pt());
+            let (w, h) = ((a.x - b.x).abs().to_pt(), (a.y - b.y).abs().to_pt());
+            if let Some(Paint::Solid(color)) = shape.fill {
+                ui.draw_rectangle(x, y, w, h, to_color32(color));
+                filled = true;
+            }
+            if let Some(stroke) = stroke
+                && let Paint::Solid(color) = stroke.paint
+                && stroke.dash.is_none()
+                && matches!(stroke.join, LineJoin::Miter)
+                && stroke.miter_limit.get() >= std::f64::consts::SQRT_2
+                && ts.sx.get().abs() == ts.sy.get().abs()
+            {
+                let thickness = (stroke.thickness.to_pt() * scale).max(1.0);
+                ui.draw_rectangle_lines(x, y, w, h, thickness, to_color32(color));
+                if filled || shape.fill.is_none() {
+                    return Some(());
                 }
-                let color = match stroke.paint {
-                    Solid(color) => color,
-                    _ => Color::BLACK,
-                };
-                // println!("origin {:?}", origin);
-                let dst = *line_to + origin;
-                // println!("origin {:?}", origin);
+            }
+        }
+        Geometry::Line(target) if shape.fill.is_none() => {
+            let Some(stroke) = stroke else {
+                return Some(());
+            };
+            if let (Paint::Solid(color), LineCap::Butt, None) =
+                (&stroke.paint, stroke.cap, &stroke.dash)
+            {
+                let origin = Point::zero().transform(ts);
+                let target = target.transform(ts);
                 ui.draw_line(
                     origin.x.to_pt(),
                     origin.y.to_pt(),
-                    dst.x.to_pt(),
-                    dst.y.to_pt(),
-                    stroke.thickness.to_pt(),
-                    color,
+                    target.x.to_pt(),
+                    target.y.to_pt(),
+                    (stroke.thickness.to_pt() * scale).max(1.0),
+                    to_color32(*color),
                 );
-                if display {
-                    tracing::debug!("draw_line {:?} {:?}", (origin, dst), color);
-                    eprintln!("draw_line {:?} {:?}", (origin, dst), color);
-                }
+                return Some(());
             }
-            Shape(s, span) => {
-                if display {
-                    tracing::debug!("{:?} {:?}", s, span)
-                };
-            }
-            Image(_, size, span) => {
+        }
+        _ => {}
+    }
+
+    let path = convert_geometry(&shape.geometry)?;
+    let sk_ts = to_sk_transform(ts);
+    let bbox = shape.geometry.bbox_size();
+
+    if let Some(fill) = &shape.fill
+        && !filled
+        && let Some(path) = path.clone().transform(sk_ts)
+    {
+        let placement = Placement::new(bbox, false);
+        fill_path(ui, &path, shape.fill_rule, fill, state, placement);
+    }
+
+    // Strokes are turned into outlines in the shape's own coordinate system,
+    // so that they are distorted along with the shape.
+    if let Some(stroke) = stroke {
+        let outline = stroke_outline(&path, stroke, scale)?.transform(sk_ts)?;
+
+        // Paints relative to the shape span its stroke, too, except for
+        // lines.
+        let placement = match shape.geometry {
+            Geometry::Line(_) => Placement::new(bbox, false),
+            _ => Placement {
+                origin: Point::splat(-stroke.thickness),
+                size: bbox + Size::splat(2.0 * stroke.thickness),
+                on_text: false,
+            },
+        };
+        fill_path(
+            ui,
+            &outline,
+            FillRule::NonZero,
+            &stroke.paint,
+            state,
+            placement,
+        );
+    }
+
+    Some(())
+}
+
+/// Convert a Typst geometry into a tiny-skia path.
+fn convert_geometry(geometry: &Geometry) -> Option<sk::Path> {
+    match geometry {
+        Geometry::Line(target) => {
+            let mut builder = sk::PathBuilder::new();
+            builder.move_to(0.0, 0.0);
+            builder.line_to(target.x.to_pt() as f32, target.y.to_pt() as f32);
+            builder.finish()
+        }
+        Geometry::Rect(size) => {
+            // Typst allows rectangles with negative dimensions, which extend
+            // to the left or top of the origin.
+            let (w, h) = (size.x.to_pt() as f32, size.y.to_pt() as f32);
+            let rect = sk::Rect::from_ltrb(w.min(0.0), h.min(0.0), w.max(0.0), h.max(0.0))?;
+            Some(sk::PathBuilder::from_rect(rect))
+        }
+        Geometry::Curve(curve) => convert_curve(curve),
+    }
+}
+
+/// Convert a Typst curve into a tiny-skia path.
+fn convert_curve(curve: &Curve) -> Option<sk::Path> {
+    let mut builder = sk::PathBuilder::new();
+    for item in &curve.0 {
+        match item {
+            CurveItem::Move(p) => builder.move_to(p.x.to_pt() as f32, p.y.to_pt() as f32),
+            CurveItem::Line(p) => builder.line_to(p.x.to_pt() as f32, p.y.to_pt() as f32),
+            CurveItem::Cubic(p1, p2, p3) => builder.cubic_to(
+                p1.x.to_pt() as f32,
+                p1.y.to_pt() as f32,
+                p2.x.to_pt() as f32,
+                p2.y.to_pt() as f32,
+                p3.x.to_pt() as f32,
+                p3.y.to_pt() as f32,
+            ),
+            CurveItem::Close => builder.close(),
+        }
+    }
+    builder.finish()
+}
+
+/// Turn the stroke of a path into an outline that can be filled, applying
+/// the dash pattern, caps and joins. The scale is the one from the path's
+/// coordinates to the screen.
+pub(super) fn stroke_outline(
+    path: &sk::Path,
+    stroke: &FixedStroke,
+    scale: f64,
+) -> Option<sk::Path> {
+    let resolution = scale as f32;
+
+    let dashed;
+    let path = match stroke.dash.as_ref().and_then(to_sk_dash_pattern) {
+        Some(dash) => {
+            dashed = path.dash(&dash, resolution)?;
+            &dashed
+        }
+        None => path,
+    };
+
+    // Strokes thinner than a pixel are drawn one pixel wide, as they would
+    // barely show up otherwise.
+    let width = (stroke.thickness.to_pt() as f32).max(1.0 / resolution);
+    let stroke = sk::Stroke {
+        width,
+        miter_limit: stroke.miter_limit.get() as f32,
+        line_cap: match stroke.cap {
+            LineCap::Butt => sk::LineCap::Butt,
+            LineCap::Round => sk::LineCap::Round,
+            LineCap::Square => sk::LineCap::Square,
+        },
+        line_join: match stroke.join {
+            LineJoin::Miter => sk::LineJoin::Miter,
+            LineJoin::Round => sk::LineJoin::Round,
+            LineJoin::Bevel => sk::LineJoin::Bevel,
+        },
+        dash: None,
+    };
+    path.stroke(&stroke, resolution)
+}
+
+/// Convert a Typst dash pattern into a tiny-skia one.
+fn to_sk_dash_pattern(dash: &DashPattern<Abs, Abs>) -> Option<sk::StrokeDash> {
+    // tiny-skia only allows dash patterns with an even number of elements,
+    // so odd ones are repeated, which doesn't change their meaning.
+    let len = dash.array.len();
+    let len = if len % 2 == 1 { 2 * len } else { len };
+    let array = dash
+        .array
+        .iter()
+        .map(|length| length.to_pt() as f32)
+        .cycle()
+        .take(len)
+        .collect();
+    sk::StrokeDash::new(array, dash.phase.to_pt() as f32)
+}
+
+/// Convert a Typst transform into a tiny-skia one.
+pub(super) fn to_sk_transform(ts: Transform) -> sk::Transform {
+    sk::Transform::from_row(
+        ts.sx.get() as f32,
+        ts.ky.get() as f32,
+        ts.kx.get() as f32,
+        ts.sy.get() as f32,
+        ts.tx.to_pt() as f32,
+        ts.ty.to_pt() as f32,
+    )
+}
+
+/// The factor by which a transform scales lengths, assuming it doesn't
+/// distort them.
+pub(super) fn scale(ts: Transform) -> f64 {
+    (ts.sx.get() * ts.sy.get() - ts.kx.get() * ts.ky.get())
+        .abs()
+        .sqrt()
+}
+
+/// Render a group, applying its transform and clip on top of the ones of
+/// its parent.
+fn render_group(ui: &mut Ui, group: &GroupItem, state: State, display: bool) {
+    let mut state = state.pre_concat(group.transform);
+    if group.frame.kind().is_hard() {
+        state.container_ts = state.ts;
+        state.container_size = group.frame.size();
+    }
+
+    let Some(path) = group
+        .clip
+        .as_ref()
+        .and_then(convert_curve)
+        .and_then(|path| path.transform(to_sk_transform(state.ts)))
+    else {
+        render_frame(ui, &group.frame, state, display);
+        return;
+    };
+
+    // Everything is clipped to the bounds of the clip path by the ui. That is
+    // exact for rectangles, which most clips are. Meshes are additionally
+    // clipped to other clip paths, such as rounded or rotated rectangles.
+    let clip = GroupClip::new(&path);
+    let previous = ui.clip_rect();
+    let rect = previous.intersect(clip.bounds());
+    if !rect.is_positive() {
+        return;
+    }
+
+    ui.set_clip_rect(rect);
+    match clip {
+        GroupClip::Path(_, path) => {
+            let mut clips = state.clips.to_vec();
+            clips.push(path);
+            render_frame(
+                ui,
+                &group.frame,
+                State {
+                    clips: &clips,
+                    ..state
+                },
+                display,
+            );
+        }
+        GroupClip::Rect(_) => {
+            render_frame(ui, &group.frame, state, display);
+        }
+    }
+    ui.set_clip_rect(previous);
+}
+
+/// Render a frame, mapping its points to the screen with the state.
+pub(super) fn render_frame(ui: &mut Ui, frame: &TypstFrame, state: State, display: bool) {
+    for (point, item) in frame.items() {
+        let state = state.pre_concat(Transform::translate(point.x, point.y));
+        match item {
+            Text(text) => render_text(ui, text, state, display),
+            Group(group) => render_group(ui, group, state, display),
+            Shape(shape, span) => {
                 if display {
-                    tracing::debug!("image {:?} {:?}", size, span);
+                    tracing::trace!(?point, ?shape, ?span, "drawing shape");
                 }
+                render_shape(ui, shape, state);
             }
-            x => {
+            Image(image, size, span) => {
                 if display {
-                    tracing::debug!("wat {:?}", x);
+                    tracing::trace!(?point, ?size, ?span, "drawing image");
                 }
+                render_image(ui, image, *size, state);
             }
+            // Links and introspection tags have no visual representation.
+            Link(..) | Tag(_) => {}
         }
     }
 }
 
+impl MyApp {
+    /// Highlight what the source at the editor's cursor produced in the
+    /// preview, scrolling to it if `reveal` is set.
+    fn sync_preview(&mut self, reveal: bool) {
+        self.synced = self.editor.cursor();
+        let regions = match (&self.source, self.synced) {
+            (Some(source), Some(cursor)) => sync::jump_from_cursor(&self.spans, source, cursor),
+            _ => vec![],
+        };
+        self.page_view.highlight(regions, reveal);
+    }
+}
+
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         handle_files(ctx);
 
-        if let Some(bytes) = self.source.take() {
-            tracing::debug!("self.renderer.render_from_slice(&bytes);");
-            let page = self.renderer.render_from_string(bytes);
-            tracing::debug!("render_from_slice done");
-            collect_font_from_frame(&mut self.font_definitions, &page);
-            ctx.set_fonts(self.font_definitions.clone());
-            self.page = Some(page);
-            println!("page update");
-            ctx.request_repaint();
-            return; // wait until next frame
+        if let Some(compiled) = self.compiler.poll() {
+            self.diagnostics = compiled.diagnostics;
+            // Keep showing the previous document if the new source has errors.
+            if let Some(document) = compiled.document {
+                // The glyphs of the document are drawn as meshes and need no
+                // fonts in egui. Changing the fonts rebuilds egui's font
+                // atlas, so the fallback is only registered once.
+                if !self.cjk_fallback {
+                    self.cjk_fallback = register_cjk_fallback(ctx, &document);
+                }
+                self.document = Some(document);
+                self.numbers = compiled.numbers;
+                self.spans = compiled.spans;
+                self.source = compiled.source;
+                self.tree = None;
+                self.display = true;
+                self.sync_preview(false);
+                tracing::debug!("showing the new document");
+            }
         }
 
         ctx.input(|i| {
@@ -165,47 +397,106 @@ impl eframe::App for MyApp {
                 } = file
                 {
                     let source = String::from_utf8(bytes.iter().copied().collect()).unwrap();
-                    self.source = Some(source);
-                    tracing::debug!("{} bytes", bytes.len());
+                    tracing::debug!(bytes = bytes.len(), "file dropped");
+                    self.editor.set_text(source.clone());
+                    self.compiler.submit(Job::Source(source));
                 } else if let DroppedFile {
                     path: Some(path), ..
                 } = file
                 {
                     let mut file = std::fs::File::open(path).unwrap();
                     let mut source = String::new();
-                    println!("{} bytes", source.len());
                     file.read_to_string(&mut source).unwrap();
-                    self.source = Some(source);
+                    tracing::debug!(?path, bytes = source.len(), "file dropped");
+                    self.editor.set_text(source.clone());
+                    self.compiler.submit(Job::Source(source));
                 }
             }
         });
 
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if self.compiler.is_busy() {
+                    ui.spinner();
+                    ui.label("Compiling…");
+                } else {
+                    let errors = self
+                        .diagnostics
+                        .iter()
+                        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                        .count();
+                    let warnings = self.diagnostics.len() - errors;
+                    ui.label(format!("{errors} errors, {warnings} warnings"));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.view, View::Text, "Source");
+                ui.selectable_value(&mut self.view, View::Tree, "Frame tree");
+                ui.separator();
+                self.page_view.navigation(ui, &self.numbers);
+            });
+        });
+
+        if !self.diagnostics.is_empty() {
+            egui::TopBottomPanel::bottom("diagnostics")
+                .resizable(true)
+                .max_height(ctx.content_rect().height() / 3.0)
+                .show(ctx, |ui| {
+                    if let Some(offset) = diagnostics::panel(ui, &self.diagnostics) {
+                        self.editor.jump_to(offset);
+                    }
+                });
+        }
+
+        // The source and the preview are shown side by side.
+        egui::SidePanel::left("editor")
+            .resizable(true)
+            .default_width(ctx.content_rect().width() / 2.0)
+            .show(ctx, |ui| match self.view {
+                View::Text => {
+                    self.page_view.hover(None, false);
+                    if self.editor.show(ui, &self.diagnostics) {
+                        self.compiler
+                            .submit(Job::Source(self.editor.text().to_owned()));
+                    }
+                }
+                View::Tree => {
+                    if self.tree.is_none() {
+                        self.tree = self
+                            .document
+                            .as_ref()
+                            .map(|document| TreeNode::new(document, &self.numbers));
+                    }
+                    let hovered = self.tree.as_ref().and_then(|tree| tree.show(ui));
+                    let region = hovered.map(|(region, _)| region);
+                    let reveal = hovered.is_some_and(|(_, clicked)| clicked);
+                    self.page_view.hover(region, reveal);
+                }
+            });
+
+        if self.editor.cursor() != self.synced {
+            self.sync_preview(true);
+        }
+
         let options = Frame {
-            fill: Color32::WHITE,
+            fill: Color32::from_gray(220),
             ..Frame::default()
         };
 
         egui::CentralPanel::default()
             .frame(options)
             .show(ctx, |ui| {
-                ui.text_edit_multiline(&mut self.input);
-
-                if ui.button("编译").clicked() {
-                    self.source = Some(self.input.clone());
-                    self.display = true;
-                    ctx.request_repaint();
-                    return;
-                }
-
-                if let Some(page) = &self.page {
-                    let mut line_count = 0;
-                    render_frame(
-                        ui,
-                        page,
-                        Point::new(Abs::pt(0.0), Abs::pt(90.0)),
-                        self.display,
-                        &mut line_count,
-                    );
+                if let Some(document) = &self.document {
+                    // Clicking on the preview moves the cursor to the source
+                    // of what was clicked.
+                    if let Some((index, point)) = self.page_view.show(ui, document, self.display)
+                        && let Some(source) = &self.source
+                        && let Some(offset) =
+                            sync::jump_from_click(source, &document.pages[index].frame, point)
+                    {
+                        self.editor.jump_to(offset);
+                    }
                     self.display = false;
                 }
             });
@@ -248,3 +539,157 @@ fn handle_files(ctx: &egui::Context) {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use typst_library::layout::{Angle, Ratio};
+    use typst_library::visualize::{Color, Curve};
+    use typst_syntax::Span;
+
+    use super::*;
+    use crate::gui::testing::{assert_similar, export, preview};
+
+    /// How far the channels of a pixel may be off. Strokes are anti-aliased
+    /// slightly differently.
+    const TOLERANCE: u8 = 10;
+
+    /// The size of the frame within the groups.
+    fn content_size() -> Size {
+        Size::new(Abs::pt(60.0), Abs::pt(40.0))
+    }
+
+    /// A frame with a filled and stroked rectangle, an ellipse and a line.
+    fn content() -> TypstFrame {
+        let size = content_size();
+        let mut rect = Geometry::Rect(size).filled(Color::from_u8(230, 240, 255, 255));
+        rect.stroke = Some(FixedStroke::from_pair(
+            Color::from_u8(20, 60, 160, 255),
+            Abs::pt(2.0),
+        ));
+        let ellipse = Geometry::Curve(Curve::ellipse(Size::new(Abs::pt(30.0), Abs::pt(20.0))))
+            .filled(Color::RED);
+        let line = Geometry::Line(Point::new(Abs::pt(50.0), Abs::pt(25.0)))
+            .stroked(FixedStroke::from_pair(Color::BLACK, Abs::pt(3.0)));
+
+        let mut frame = TypstFrame::soft(size);
+        frame.push(Point::zero(), Shape(rect, Span::detached()));
+        frame.push(
+            Point::new(Abs::pt(25.0), Abs::pt(5.0)),
+            Shape(ellipse, Span::detached()),
+        );
+        frame.push(
+            Point::new(Abs::pt(5.0), Abs::pt(10.0)),
+            Shape(line, Span::detached()),
+        );
+        frame
+    }
+
+    /// A page with the content in a group with the transform and clip. The
+    /// transform is applied around the center of the content.
+    fn page(transform: Transform, clip: Option<Curve>) -> TypstFrame {
+        let size = content_size();
+        let center = Transform::translate(size.x / 2.0, size.y / 2.0);
+        let mut group = GroupItem::new(content());
+        group.transform = center
+            .pre_concat(transform)
+            .pre_concat(center.invert().unwrap());
+        group.clip = clip;
+
+        let mut frame = TypstFrame::soft(Size::splat(Abs::pt(120.0)));
+        frame.push(Point::new(Abs::pt(30.0), Abs::pt(40.0)), Group(group));
+        frame
+    }
+
+    /// A rectangle within the content, as a curve for clipping.
+    fn clip_rect() -> Curve {
+        let mut curve = Curve::rect(Size::new(Abs::pt(40.0), Abs::pt(25.0)));
+        curve.translate(Point::new(Abs::pt(12.0), Abs::pt(8.0)));
+        curve
+    }
+
+    #[test]
+    fn test_rotated_group_matches_export() {
+        let frame = page(Transform::rotate(Angle::deg(30.0)), None);
+        assert_similar(&preview(&frame), &export(&frame), TOLERANCE);
+    }
+
+    #[test]
+    fn test_scaled_group_matches_export() {
+        for (sx, sy) in [(1.5, 0.75), (-1.0, 1.0), (0.5, -1.25)] {
+            let ts = Transform::scale(Ratio::new(sx), Ratio::new(sy));
+            let frame = page(ts, None);
+            assert_similar(&preview(&frame), &export(&frame), TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn test_skewed_group_matches_export() {
+        let frame = page(Transform::skew(Angle::deg(20.0), Angle::deg(-10.0)), None);
+        assert_similar(&preview(&frame), &export(&frame), TOLERANCE);
+    }
+
+    #[test]
+    fn test_clipped_group_matches_export() {
+        let frame = page(Transform::identity(), Some(clip_rect()));
+        assert_similar(&preview(&frame), &export(&frame), TOLERANCE);
+    }
+
+    /// A five-pointed star within the content, as a curve for clipping.
+    fn clip_star() -> Curve {
+        let mut curve = Curve::new();
+        for i in 0..10 {
+            let radius = if i % 2 == 0 { 20.0 } else { 8.0 };
+            let angle = Angle::deg(36.0 * i as f64 - 90.0);
+            let p = Point::new(
+                Abs::pt(30.0 + radius * angle.cos()),
+                Abs::pt(20.0 + radius * angle.sin()),
+            );
+            if i == 0 {
+                curve.move_(p)
+            } else {
+                curve.line(p)
+            }
+        }
+        curve.close();
+        curve
+    }
+
+    /// A rectangle within the content whose top and bottom edges curve
+    /// inwards, with a hole that winds the other way.
+    fn clip_curved() -> Curve {
+        let pt = |x, y| Point::new(Abs::pt(x), Abs::pt(y));
+        let mut curve = Curve::new();
+        curve.move_(pt(5.0, 2.0));
+        curve.cubic(pt(20.0, 18.0), pt(40.0, 18.0), pt(55.0, 2.0));
+        curve.line(pt(55.0, 38.0));
+        curve.cubic(pt(40.0, 22.0), pt(20.0, 22.0), pt(5.0, 38.0));
+        curve.close();
+        curve.move_(pt(10.0, 15.0));
+        curve.line(pt(10.0, 25.0));
+        curve.line(pt(16.0, 25.0));
+        curve.line(pt(16.0, 15.0));
+        curve.close();
+        curve
+    }
+
+    #[test]
+    fn test_non_convex_clipped_group_matches_export() {
+        for ts in [Transform::identity(), Transform::rotate(Angle::deg(15.0))] {
+            for clip in [clip_star(), clip_curved()] {
+                let frame = page(ts, Some(clip));
+                assert_similar(&preview(&frame), &export(&frame), TOLERANCE);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotated_clipped_group_matches_export() {
+        // The rotated clip is no longer an axis-aligned rectangle, and the
+        // ellipse is convex, so both are clipped to polygons.
+        let rotate = Transform::rotate(Angle::deg(-25.0));
+        for clip in [clip_rect(), Curve::ellipse(content_size())] {
+            let frame = page(rotate, Some(clip));
+            assert_similar(&preview(&frame), &export(&frame), TOLERANCE);
+        }
+    }
+}
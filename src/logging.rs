@@ -0,0 +1,34 @@
+use std::io::{self, IsTerminal};
+
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::time::Uptime;
+
+use crate::args::CliArguments;
+
+/// Print the tracing events of this crate to stderr.
+///
+/// Only warnings are shown by default. Each `-v` lowers the level by one step
+/// and `-q` hides everything. If `RUST_LOG` is set, its filter is used
+/// instead. When the phases of a compilation are shown, they are logged as
+/// they end, along with how long they took.
+pub fn init(args: &CliArguments) {
+    // Dependencies that log through `tracing` stay at warnings, so that the
+    // flags only reveal what this crate is doing.
+    let directives = match (args.quiet, args.verbose) {
+        (true, _) => "off",
+        (false, 0) => "warn",
+        (false, 1) => "warn,main=info",
+        (false, 2) => "warn,main=debug",
+        (false, _) => "warn,main=trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(directives));
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_timer(Uptime::default())
+        .init();
+}
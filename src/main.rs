@@ -6,189 +6,492 @@ use std::{
 };
 use typst_library::foundations::{NativeRuleMap, StyleChain};
 
+use chrono::{DateTime, Datelike, FixedOffset, Local, Timelike, Utc};
 use comemo::{Track, Tracked};
-use std::sync::LazyLock;
+use std::sync::{LazyLock, OnceLock};
 
 use typst_library::World;
 use typst_library::engine::{Engine, Route, Sink, Traced};
 use typst_library::routines::Routines;
-use typst_timing::timed;
+use typst_timing::{TimingScope, timed};
 
 use typst::LibraryExt;
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use parking_lot::Mutex;
-use typst_kit::fonts::{FontSlot, Fonts};
 use typst_library::text::{Font, FontBook};
 
 use typst_library::foundations::{Bytes, Dict, IntoValue, TargetElem};
 use typst_library::introspection::Introspector;
 
-use ecow::eco_format;
+use ecow::{EcoString, EcoVec, eco_format, eco_vec};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use typst_html::HtmlDocument;
 use typst_library::diag::{
-    At, FileError, FileResult, HintedString, SourceResult, StrResult, Warned, bail,
+    At, FileError, FileResult, HintedStrResult, HintedString, SourceDiagnostic, SourceResult,
+    StrResult, Warned, bail, warning,
 };
 use typst_library::foundations::{Datetime, Smart};
-use typst_library::layout::{Frame, Page, PageRanges, PagedDocument};
+use typst_library::layout::{Page, PageRanges, PagedDocument};
 use typst_library::{Library, model::DocumentInfo};
-use typst_pdf::{PdfOptions, PdfStandards};
+use typst_pdf::{PdfOptions, PdfStandards, Timestamp};
 use typst_syntax::{FileId, Lines, Source, Span, VirtualPath};
 
-use typst_utils::{LazyHash, hash128};
+use typst_utils::LazyHash;
 
+mod args;
+mod deps;
+mod diagnostics;
+mod download;
+mod font_manifest;
+mod fonts;
 mod gui;
-pub fn main() -> StrResult<()> {
-    fn help() {
-        println!("Usage: typst (compile|render) <input_file>");
-    }
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 3 {
-        help();
-        return Ok(());
-    }
-    match args[1].as_str() {
-        "compile" => {
-            Renderer::new()
-                .render_from_path_to_pdf(&"main.typ".into())
-                .unwrap();
-            Ok(())
+mod logging;
+mod package;
+mod query;
+mod terminal;
+mod timings;
+mod watch;
+
+use std::cell::Cell;
+use std::process::ExitCode;
+
+use clap::Parser;
+use codespan_reporting::term;
+use codespan_reporting::term::termcolor::WriteColor;
+
+use crate::args::{
+    CliArguments, Command, CompileArgs, DepsFormat, DiagnosticFormat, Input, Output, OutputFormat,
+    Pages, PdfStandard, ProcessArgs, RenderCommand, WorldArgs,
+};
+use crate::diagnostics::print_diagnostics;
+use crate::fonts::FontSlot;
+use crate::package::PackageStorage;
+use crate::timings::Timer;
+use crate::watch::Status;
+
+thread_local! {
+    /// The CLI's exit code.
+    static EXIT: Cell<ExitCode> = const { Cell::new(ExitCode::SUCCESS) };
+}
+
+/// Entry point.
+pub fn main() -> ExitCode {
+    let res = dispatch();
+
+    if let Err(msg) = res {
+        set_failed();
+        print_error(msg.message()).expect("failed to print error");
+        for hint in msg.hints() {
+            print_hint(hint).expect("failed to print hint");
         }
-        "image" => {
-            Renderer::new()
-                .render_from_path_to_image(&"main.typ".into())
-                .unwrap();
-            Ok(())
+    }
+
+    EXIT.with(|cell| cell.get())
+}
+
+/// Execute the requested command.
+fn dispatch() -> HintedStrResult<()> {
+    let args = CliArguments::parse();
+    logging::init(&args);
+    let mut timer = Timer::new(&args);
+
+    match &args.command {
+        Command::Compile(command) => compile_command(&command.args, None, &mut timer)?,
+        Command::Image(command) => {
+            compile_command(&command.args, Some(OutputFormat::Png), &mut timer)?
+        }
+        Command::Html(command) => {
+            compile_command(&command.args, Some(OutputFormat::Html), &mut timer)?
         }
-        "html" => {
-            Renderer::new()
-                .render_from_path_to_html(&"main.typ".into())
-                .unwrap();
-            Ok(())
+        Command::Watch(command) => watch::watch(&command.args, &mut timer)?,
+        Command::Render(command) => render(command)?,
+        Command::Query(command) => query::query(command)?,
+        Command::Fonts(command) => fonts::fonts(command)?,
+    }
+
+    Ok(())
+}
+
+/// Ensure a failure exit code.
+fn set_failed() {
+    EXIT.with(|cell| cell.set(ExitCode::FAILURE));
+}
+
+/// Print an application-level error (independent from a source file).
+fn print_error(msg: &str) -> io::Result<()> {
+    let styles = term::Styles::default();
+
+    let mut output = terminal::out();
+    output.set_color(&styles.header_error)?;
+    write!(output, "error")?;
+
+    output.reset()?;
+    writeln!(output, ": {msg}")
+}
+
+/// Print an application-level hint (independent from a source file).
+fn print_hint(msg: &str) -> io::Result<()> {
+    let styles = term::Styles::default();
+
+    let mut output = terminal::out();
+    output.set_color(&styles.header_help)?;
+    write!(output, "hint")?;
+
+    output.reset()?;
+    writeln!(output, ": {msg}")
+}
+
+/// Execute a compilation command, defaulting to the given format when neither
+/// `--format` nor the output extension determine one.
+fn compile_command(
+    args: &CompileArgs,
+    default_format: Option<OutputFormat>,
+    timer: &mut Timer,
+) -> HintedStrResult<()> {
+    let config = Renderer::compile_config(args, default_format, false)?;
+    let mut renderer = Renderer::new(&args.input, &args.world, &args.process)?;
+    timer.record(&mut renderer, |renderer| compile_once(renderer, &config))??;
+    Ok(())
+}
+
+/// Compile and export a single time, printing diagnostics and, in watch mode,
+/// the status.
+///
+/// Returns the written outputs, which are empty if compilation failed.
+fn compile_once(renderer: &mut Renderer, config: &CompileConfig) -> HintedStrResult<Vec<Output>> {
+    let start = std::time::Instant::now();
+    if config.watching {
+        Status::Compiling.print(config).unwrap();
+    }
+
+    let Warned {
+        output,
+        mut warnings,
+    } = renderer.render_to_output(config);
+
+    // Add static warnings (for questionable CLI flags and such).
+    for warning in config.warnings.iter() {
+        warnings.push(
+            SourceDiagnostic::warning(Span::detached(), warning.message())
+                .with_hints(warning.hints().iter().map(Into::into)),
+        );
+    }
+
+    let outputs = match output {
+        Ok(outputs) => {
+            let duration = start.elapsed();
+            if config.watching {
+                if warnings.is_empty() {
+                    Status::Success(duration).print(config).unwrap();
+                } else {
+                    Status::PartialSuccess(duration).print(config).unwrap();
+                }
+            }
+
+            renderer.report(&[], &warnings)?;
+            Some(outputs)
         }
-        "render" => render(),
-        _ => {
-            help();
-            Ok(())
+        Err(errors) => {
+            set_failed();
+
+            if config.watching {
+                Status::Error.print(config).unwrap();
+            }
+
+            renderer.report(&errors, &warnings)?;
+            None
         }
+    };
+
+    if let Some(dest) = &config.deps {
+        timed!(
+            "write dependencies",
+            deps::write_deps(
+                &mut renderer.world,
+                dest,
+                config.deps_format,
+                outputs.as_deref(),
+            )
+        )
+        .map_err(|err| eco_format!("failed to create dependency file ({err})"))?;
     }
+
+    Ok(outputs.unwrap_or_default())
 }
 
 struct Renderer {
     world: SystemWorld,
+    /// The format to emit diagnostics in.
+    diagnostic_format: DiagnosticFormat,
 }
 
 impl Renderer {
-    fn new() -> Self {
-        let font_args: FontArgs = FontArgs {
-            font_paths: Vec::new(),
-            ignore_system_fonts: false,
+    fn new(
+        input: &Input,
+        world_args: &WorldArgs,
+        process_args: &ProcessArgs,
+    ) -> HintedStrResult<Self> {
+        let world = SystemWorld::new(input, world_args, process_args)?;
+        Ok(Self {
+            world,
+            diagnostic_format: process_args.diagnostic_format,
+        })
+    }
+    /// Print errors and warnings of the last compilation to the terminal.
+    fn report(&self, errors: &[SourceDiagnostic], warnings: &[SourceDiagnostic]) -> StrResult<()> {
+        print_diagnostics(&self.world, errors, warnings, self.diagnostic_format)
+            .map_err(|err| eco_format!("failed to print diagnostics ({err})"))
+    }
+    /// Compile the main file, report its diagnostics and return the document
+    /// along with them. Selected pages beyond the end of the document are
+    /// warned about, but all pages are kept.
+    fn render_document(&self, pages: Option<&[Pages]>) -> Warned<SourceResult<PagedDocument>> {
+        let Warned {
+            output,
+            mut warnings,
+        } = compile::<PagedDocument>(&self.world);
+        if let (Ok(doc), Some(pages)) = (&output, pages) {
+            warnings.extend(check_page_ranges(pages, doc.pages.len()));
+        }
+        let errors = output.as_ref().err().cloned().unwrap_or_default();
+        if let Err(err) = self.report(&errors, &warnings) {
+            print_error(&err).ok();
+        }
+        Warned { output, warnings }
+    }
+    /// Make the file at the path the main file and render it.
+    fn render_from_path(
+        &mut self,
+        path: &Path,
+        pages: Option<&[Pages]>,
+    ) -> Warned<SourceResult<PagedDocument>> {
+        if let Some(vpath) = path
+            .canonicalize()
+            .ok()
+            .and_then(|path| VirtualPath::within_root(&path, self.world.root()))
+        {
+            self.world.main = FileId::new(None, vpath);
+        }
+        self.render_document(pages)
+    }
+    fn compile_config(
+        args: &CompileArgs,
+        default_format: Option<OutputFormat>,
+        watching: bool,
+    ) -> HintedStrResult<CompileConfig> {
+        let input = args.input.clone();
+
+        let output_format = if let Some(specified) = args.format {
+            specified
+        } else if let Some(Output::Path(output)) = &args.output {
+            match output.extension() {
+                Some(ext) if ext.eq_ignore_ascii_case("pdf") => OutputFormat::Pdf,
+                Some(ext) if ext.eq_ignore_ascii_case("png") => OutputFormat::Png,
+                Some(ext) if ext.eq_ignore_ascii_case("svg") => OutputFormat::Svg,
+                Some(ext) if ext.eq_ignore_ascii_case("html") => OutputFormat::Html,
+                _ => match default_format {
+                    Some(format) => format,
+                    None => bail!(
+                        "could not infer output format for path {}.\n\
+                         consider providing the format manually with `--format/-f`",
+                        output.display()
+                    ),
+                },
+            }
+        } else {
+            default_format.unwrap_or(OutputFormat::Pdf)
         };
-        let package = PackageArgs::default();
-        let world_args = WorldArgs {
-            root: None,
-            inputs: Vec::new(),
-            font: font_args,
-            package: package,
+
+        let output = match (&args.output, &input) {
+            (Some(output), _) => output.clone(),
+            (None, Input::Path(path)) => {
+                Output::Path(path.with_extension(output_format.extension()))
+            }
+            (None, Input::Stdin) => bail!("output must be specified when input is from stdin"),
         };
-        let process_args = ProcessArgs { jobs: None };
-        Self {
-            world: SystemWorld::new(&Input::Stdin, &world_args, &process_args).unwrap(),
+
+        if watching && matches!(output, Output::Stdout) {
+            bail!("cannot write document to stdout in watch mode");
         }
-    }
-    fn render_from_path(&mut self, path: &PathBuf) -> Frame {
-        println!("render_from_path");
-        self.world.main = FileId::new(None, VirtualPath::new(path));
-        let Warned { output, .. } = compile::<PagedDocument>(&mut self.world);
-        let doc: PagedDocument = output.unwrap();
-        doc.pages.into_iter().next().unwrap().frame
-    }
-    fn compile_config(output_path: PathBuf, output_format: OutputFormat) -> CompileConfig {
-        let output = Output::Path(output_path);
-        let pdf_standards = PdfStandards::default();
-        let deps_format = DepsFormat::default();
-        let config = CompileConfig {
-            warnings: Vec::new(),
-            watching: false,
-            input: Input::Stdin,
+
+        // Every compilation is recorded into a file of its own.
+        if watching
+            && let Some(timings) = &args.timings
+            && !timings.to_string_lossy().contains("{n}")
+        {
+            bail!(
+                "cannot record the timings of multiple compilations without `{{n}}` in the path";
+                hint: "`{{n}}` is replaced with the number of the compilation, starting at zero"
+            );
+        }
+
+        let mut warnings = Vec::new();
+        if args.pages.is_some() && output_format == OutputFormat::Html {
+            warnings.push(HintedString::from(eco_format!(
+                "the `--pages` argument is ignored for HTML export"
+            )));
+        }
+        if output_format != OutputFormat::Pdf {
+            if !args.pdf_standard.is_empty() {
+                warnings.push(HintedString::from(eco_format!(
+                    "the `--pdf-standard` argument is ignored for {output_format} export"
+                )));
+            }
+            if args.pdf_tags {
+                warnings.push(HintedString::from(eco_format!(
+                    "the `--pdf-tags` argument is ignored for {output_format} export"
+                )));
+            }
+        }
+
+        let (pdf_standards, tagged) = if output_format == OutputFormat::Pdf {
+            // Tags are required by the accessible standards, but cannot be
+            // written when only some of the pages are exported.
+            const ACCESSIBLE: &[(PdfStandard, &str)] = &[
+                (PdfStandard::A_1a, "PDF/A-1a"),
+                (PdfStandard::A_2a, "PDF/A-2a"),
+                (PdfStandard::A_3a, "PDF/A-3a"),
+                (PdfStandard::UA_1, "PDF/UA-1"),
+            ];
+            let accessible = ACCESSIBLE
+                .iter()
+                .find(|(standard, _)| args.pdf_standard.contains(standard))
+                .map(|&(_, name)| name);
+            if let Some(name) = accessible
+                && args.pages.is_some()
+            {
+                bail!(
+                    "cannot export a {name} document with `--pages`";
+                    hint: "{name} requires PDF tags, which cannot be written for some of the pages only"
+                );
+            }
+            if args.pdf_tags && args.pages.is_some() {
+                warnings.push(
+                    HintedString::from(eco_format!(
+                        "the `--pdf-tags` argument is ignored when `--pages` is used"
+                    ))
+                    .with_hints(["the resulting PDF will be inaccessible".into()]),
+                );
+            }
+
+            let pdf_standards = PdfStandards::new(
+                &args
+                    .pdf_standard
+                    .iter()
+                    .copied()
+                    .map(Into::into)
+                    .collect::<Vec<_>>(),
+            )?;
+            let tagged = (args.pdf_tags || accessible.is_some()) && args.pages.is_none();
+            (pdf_standards, tagged)
+        } else {
+            (PdfStandards::default(), false)
+        };
+
+        Ok(CompileConfig {
+            warnings,
+            watching,
+            input,
             output,
             output_format,
-            pages: None,
+            pages: args.pages.as_deref().map(page_ranges),
+            page_args: args.pages.clone(),
             open: None,
+            creation_timestamp: args.world.creation_timestamp,
             pdf_standards,
-            tagged: false,
-            deps: None,
-            deps_format,
-            ppi: 120.0,
-        };
-        config
-    }
-    fn render_from_path_to_image(&mut self, path: &PathBuf) -> SourceResult<()> {
-        self.world.main = FileId::new(None, VirtualPath::new(path));
-        let Warned { output, .. } = compile::<PagedDocument>(&mut self.world);
-        let doc: PagedDocument = output.unwrap();
-        let config = Self::compile_config("main.png".into(), OutputFormat::Png);
-        export_paged(&doc, &config)?;
-        Ok(())
-    }
-    fn render_from_path_to_html(&mut self, path: &PathBuf) -> SourceResult<()> {
-        self.world.main = FileId::new(None, VirtualPath::new(path));
-        let Warned { output, .. } = compile::<HtmlDocument>(&mut self.world);
-        let doc = output.unwrap();
-        let config = Self::compile_config("main.html".into(), OutputFormat::Html);
-        export_html(&doc, &config)
-    }
-    fn render_from_path_to_pdf(&mut self, path: &PathBuf) -> SourceResult<()> {
-        self.world.main = FileId::new(None, VirtualPath::new(path));
-        let Warned { output, .. } = compile::<PagedDocument>(&mut self.world);
-        let doc: PagedDocument = output.unwrap();
-        let config = Self::compile_config("main.pdf".into(), OutputFormat::Pdf);
-        export_paged(&doc, &config)?;
-        Ok(())
-    }
-    fn render_from_string(&mut self, data: String) -> Frame {
-        let file = FileId::new(None, VirtualPath::new(&PathBuf::new()));
-        let fingerprint = hash128(data.as_bytes());
-        let source = Source::new(file, data);
-        let slot = FileSlot {
-            id: file,
-            source: SlotCell {
-                data: Some(Ok(source)),
-                fingerprint,
-                accessed: true,
-            },
-            file: SlotCell {
-                data: None,
-                fingerprint,
-                accessed: true,
-            },
-        };
-        self.world.slots.lock().insert(file, slot);
-        self.world.main = file;
-        // self.world.source = Some(data);
-        let Warned { output, .. } = compile::<PagedDocument>(&mut self.world);
-        let doc: PagedDocument = output.unwrap();
-        doc.pages.into_iter().next().unwrap().frame
+            tagged,
+            deps: args.deps.clone(),
+            deps_format: args.deps_format,
+            ppi: args.ppi,
+        })
+    }
+    /// Compile the main file and export it as described by the config.
+    fn render_to_output(&mut self, config: &CompileConfig) -> Warned<SourceResult<Vec<Output>>> {
+        match config.output_format {
+            OutputFormat::Html => {
+                let Warned { output, warnings } = compile::<HtmlDocument>(&self.world);
+                let result = output.and_then(|doc| export_html(&doc, config));
+                Warned {
+                    output: result.map(|()| vec![config.output.clone()]),
+                    warnings,
+                }
+            }
+            _ => {
+                let Warned {
+                    output,
+                    mut warnings,
+                } = compile::<PagedDocument>(&self.world);
+                if let (Ok(doc), Some(pages)) = (&output, &config.page_args) {
+                    warnings.extend(check_page_ranges(pages, doc.pages.len()));
+                }
+                let result = output.and_then(|doc| export_paged(&doc, config));
+                Warned {
+                    output: result,
+                    warnings,
+                }
+            }
+        }
+    }
+    /// Render the given text in place of the main file's contents.
+    ///
+    /// The main file keeps its id, so that relative paths in the text are
+    /// resolved, diagnostics are reported and spans are looked up just like
+    /// when the file is read from disk. Without a file, the text becomes a
+    /// `main.typ` in the project root.
+    fn render_from_string(
+        &mut self,
+        data: String,
+        pages: Option<&[Pages]>,
+    ) -> Warned<SourceResult<PagedDocument>> {
+        if self.world.main == *STDIN_ID {
+            self.world.main = FileId::new(None, VirtualPath::new("main.typ"));
+        }
+        let main = self.world.main;
+        self.world
+            .slots
+            .get_mut()
+            .entry(main)
+            .or_insert_with(|| FileSlot::new(main))
+            .replace_source(data);
+        self.render_document(pages)
     }
 }
 pub fn compile<D>(world: &dyn World) -> Warned<SourceResult<D>>
 where
     D: Document,
 {
+    let _span = tracing::info_span!("compile", target = ?D::TARGET).entered();
+    let _scope = TimingScope::new("compile");
     let mut sink = Sink::new();
-    let output = compile_impl::<D>(world.track(), Traced::default().track(), &mut sink);
+    let output =
+        compile_impl::<D>(world.track(), Traced::default().track(), &mut sink).map_err(deduplicate);
     Warned {
         output,
         warnings: sink.warnings(),
     }
 }
 
-fn render() -> StrResult<()> {
-    let path = Some(PathBuf::from("main.typ"));
+/// Deduplicate diagnostics.
+fn deduplicate(mut diags: EcoVec<SourceDiagnostic>) -> EcoVec<SourceDiagnostic> {
+    let mut unique = FxHashSet::default();
+    diags.retain(|diag| {
+        let hash = typst_utils::hash128(&(&diag.span, &diag.message));
+        unique.insert(hash)
+    });
+    diags
+}
+
+fn render(command: &RenderCommand) -> HintedStrResult<()> {
+    let input = Input::Path(command.input.clone());
+    let renderer = Renderer::new(&input, &command.world, &command.process)?;
 
-    gui::run(path, Renderer::new());
+    gui::run(
+        Some(command.input.clone()),
+        command.pages.as_deref(),
+        renderer,
+    );
 
     Ok(())
 }
@@ -209,6 +512,14 @@ pub struct SystemWorld {
     fonts: Vec<FontSlot>,
     /// Maps file ids to source files and buffers.
     slots: Mutex<FxHashMap<FileId, FileSlot>>,
+    /// Holds information about where packages are stored.
+    package_storage: PackageStorage,
+    /// Additional directories and files that were searched for fonts.
+    font_paths: Vec<PathBuf>,
+    /// The current datetime if requested. This is stored here to ensure it is
+    /// always the same within one compilation.
+    /// Reset between compilations if not [`Now::Fixed`].
+    now: Now,
 }
 
 /// An error that occurs during world construction.
@@ -222,6 +533,8 @@ pub enum WorldCreationError {
     RootNotFound(PathBuf),
     /// Another type of I/O error.
     Io(io::Error),
+    /// The fonts could not be found or do not match their lockfile.
+    Fonts(HintedString),
 }
 
 impl fmt::Display for WorldCreationError {
@@ -241,57 +554,18 @@ impl fmt::Display for WorldCreationError {
                 )
             }
             WorldCreationError::Io(err) => write!(f, "{err}"),
+            WorldCreationError::Fonts(err) => write!(f, "{}", err.message()),
         }
     }
 }
 
-/// Arguments related to where packages are stored in the system.
-#[derive(Debug, Clone, Default)]
-pub struct PackageArgs {
-    /// Custom path to local packages, defaults to system-dependent location.
-    pub package_path: Option<PathBuf>,
-
-    /// Custom path to package cache, defaults to system-dependent location.
-    pub package_cache_path: Option<PathBuf>,
-}
-
-/// Common arguments to customize available fonts.
-#[derive(Debug, Clone)]
-pub struct FontArgs {
-    /// Adds additional directories that are recursively searched for fonts.
-    ///
-    /// If multiple paths are specified, they are separated by the system's path
-    /// separator (`:` on Unix-like systems and `;` on Windows).
-    pub font_paths: Vec<PathBuf>,
-
-    /// Ensures system fonts won't be searched, unless explicitly included via
-    /// `--font-path`.
-    pub ignore_system_fonts: bool,
-}
-
-/// Arguments for the construction of a world. Shared by compile, watch, and
-/// query.
-#[derive(Debug, Clone)]
-pub struct WorldArgs {
-    /// Configures the project root (for absolute paths).
-    pub root: Option<PathBuf>,
-
-    /// Add a string key-value pair visible through `sys.inputs`.
-    pub inputs: Vec<(String, String)>,
-
-    /// Common font arguments.
-    pub font: FontArgs,
-
-    /// Arguments related to storage of packages in the system.
-    pub package: PackageArgs,
-}
-
-/// Arguments for configuration the process of compilation itself.
-#[derive(Debug, Clone)]
-pub struct ProcessArgs {
-    /// Number of parallel jobs spawned during compilation. Defaults to number
-    /// of CPUs. Setting it to 1 disables parallelism.
-    pub jobs: Option<usize>,
+impl From<WorldCreationError> for HintedString {
+    fn from(err: WorldCreationError) -> Self {
+        match err {
+            WorldCreationError::Fonts(err) => err,
+            err => eco_format!("{err}").into(),
+        }
+    }
 }
 
 impl SystemWorld {
@@ -353,10 +627,8 @@ impl SystemWorld {
             Library::builder().with_inputs(inputs).build()
         };
 
-        let mut fonts = Fonts::searcher();
-        fonts.include_system_fonts(!world_args.font.ignore_system_fonts);
-        fonts.include_embedded_fonts(true);
-        let fonts = fonts.search_with(&world_args.font.font_paths);
+        let (fonts, font_paths) =
+            fonts::search(&world_args.font).map_err(WorldCreationError::Fonts)?;
 
         Ok(Self {
             workdir: std::env::current_dir().ok(),
@@ -366,6 +638,12 @@ impl SystemWorld {
             book: LazyHash::new(fonts.book),
             fonts: fonts.fonts,
             slots: Mutex::new(FxHashMap::default()),
+            package_storage: PackageStorage::new(&world_args.package),
+            font_paths,
+            now: match world_args.creation_timestamp {
+                Some(time) => Now::Fixed(time),
+                None => Now::System(OnceLock::new()),
+            },
         })
     }
 
@@ -384,13 +662,18 @@ impl SystemWorld {
         self.workdir.as_deref().unwrap_or(Path::new("."))
     }
 
+    /// The additional directories and files that were searched for fonts.
+    pub fn font_paths(&self) -> &[PathBuf] {
+        &self.font_paths
+    }
+
     /// Return all paths the last compilation depended on.
     pub fn dependencies(&mut self) -> impl Iterator<Item = PathBuf> + '_ {
         self.slots
             .get_mut()
             .values()
             .filter(|slot| slot.accessed())
-            .filter_map(|slot| system_path(&self.root, slot.id).ok())
+            .filter_map(|slot| system_path(&self.root, slot.id, &self.package_storage).ok())
     }
 
     /// Reset the compilation state in preparation of a new compilation.
@@ -399,6 +682,9 @@ impl SystemWorld {
         for slot in self.slots.get_mut().values_mut() {
             slot.reset();
         }
+        if let Now::System(time_lock) = &mut self.now {
+            time_lock.take();
+        }
     }
 
     /// Lookup line metadata for a file by id.
@@ -432,13 +718,13 @@ impl World for SystemWorld {
     }
 
     fn source(&self, id: FileId) -> FileResult<Source> {
-        println!(".source");
-        self.slot(id, |slot| slot.source(&self.root))
+        tracing::trace!(?id, "source requested");
+        self.slot(id, |slot| slot.source(&self.root, &self.package_storage))
     }
 
     fn file(&self, id: FileId) -> FileResult<Bytes> {
-        println!(".file");
-        self.slot(id, |slot| slot.file(&self.root))
+        tracing::trace!(?id, "file requested");
+        self.slot(id, |slot| slot.file(&self.root, &self.package_storage))
     }
 
     fn font(&self, index: usize) -> Option<Font> {
@@ -447,8 +733,29 @@ impl World for SystemWorld {
         self.fonts.get(index)?.get()
     }
 
-    fn today(&self, _offset: Option<i64>) -> Option<Datetime> {
-        None
+    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+        let (now, fixed) = match &self.now {
+            Now::Fixed(time) => (time, true),
+            Now::System(time) => (time.get_or_init(Utc::now), false),
+        };
+
+        // The time with the specified UTC offset, or within the local time
+        // zone. A fixed time is in UTC, so that it gives the same date on
+        // every machine.
+        let with_offset = match offset {
+            None if fixed => now.fixed_offset(),
+            None => now.with_timezone(&Local).fixed_offset(),
+            Some(hours) => {
+                let seconds = i32::try_from(hours).ok()?.checked_mul(3600)?;
+                now.with_timezone(&FixedOffset::east_opt(seconds)?)
+            }
+        };
+
+        Datetime::from_ymd(
+            with_offset.year(),
+            with_offset.month().try_into().ok()?,
+            with_offset.day().try_into().ok()?,
+        )
     }
 }
 
@@ -463,6 +770,15 @@ impl SystemWorld {
     }
 }
 
+/// The current date and time.
+enum Now {
+    /// The date and time if `--creation-timestamp` or the environment
+    /// variable `SOURCE_DATE_EPOCH` is set. Used for reproducible builds.
+    Fixed(DateTime<Utc>),
+    /// The current date and time if the time is not externally fixed.
+    System(OnceLock<DateTime<Utc>>),
+}
+
 /// Holds the processed data for a file ID.
 ///
 /// Both fields can be populated if the file is both imported and read().
@@ -497,11 +813,35 @@ impl FileSlot {
         self.file.reset();
     }
 
+    /// Use the text as the file's source until the next reset instead of
+    /// reading it.
+    fn replace_source(&mut self, text: String) {
+        // The fingerprint is the one of the same text read from disk, so that
+        // the source is kept when the file turns out to have the same text.
+        let fingerprint = typst_utils::hash128(&FileResult::Ok(text.as_bytes().to_vec()));
+        let source = match self.source.data.take() {
+            Some(Ok(mut prev)) => {
+                prev.replace(&text);
+                prev
+            }
+            _ => Source::new(self.id, text),
+        };
+        self.source = SlotCell {
+            data: Some(Ok(source)),
+            fingerprint,
+            accessed: true,
+        };
+    }
+
     /// Retrieve the source for this file.
-    fn source(&mut self, project_root: &Path) -> FileResult<Source> {
-        println!("sourcing {project_root:?}");
+    fn source(
+        &mut self,
+        project_root: &Path,
+        package_storage: &PackageStorage,
+    ) -> FileResult<Source> {
+        tracing::trace!(id = ?self.id, root = %project_root.display(), "retrieving source");
         self.source.get_or_init(
-            || read(self.id, project_root),
+            || read(self.id, project_root, package_storage),
             |data, prev| {
                 let text = decode_utf8(&data)?;
                 if let Some(mut prev) = prev {
@@ -515,9 +855,9 @@ impl FileSlot {
     }
 
     /// Retrieve the file's bytes.
-    fn file(&mut self, project_root: &Path) -> FileResult<Bytes> {
+    fn file(&mut self, project_root: &Path, package_storage: &PackageStorage) -> FileResult<Bytes> {
         self.file.get_or_init(
-            || read(self.id, project_root),
+            || read(self.id, project_root, package_storage),
             |data, _| Ok(Bytes::new(data)),
         )
     }
@@ -535,22 +875,33 @@ fn decode_utf8(buf: &[u8]) -> FileResult<&str> {
 ///
 /// If the ID represents stdin it will read from standard input,
 /// otherwise it gets the file path of the ID and reads the file from disk.
-fn read(id: FileId, project_root: &Path) -> FileResult<Vec<u8>> {
+fn read(id: FileId, project_root: &Path, package_storage: &PackageStorage) -> FileResult<Vec<u8>> {
     if id == *STDIN_ID {
         read_from_stdin()
     } else {
-        read_from_disk(&system_path(project_root, id)?)
+        read_from_disk(&system_path(project_root, id, package_storage)?)
     }
 }
 
 /// Resolves the path of a file id on the system, downloading a package if
 /// necessary.
-fn system_path(project_root: &Path, id: FileId) -> FileResult<PathBuf> {
+fn system_path(
+    project_root: &Path,
+    id: FileId,
+    package_storage: &PackageStorage,
+) -> FileResult<PathBuf> {
+    // Determine the root path relative to which the file path
+    // will be resolved.
+    let buf;
+    let mut root = project_root;
+    if let Some(spec) = id.package() {
+        buf = package_storage.prepare_package(spec)?;
+        root = &buf;
+    }
+
     // Join the path to the root. If it tries to escape, deny
     // access. Note: It can still escape via symlinks.
-    id.vpath()
-        .resolve(project_root)
-        .ok_or(FileError::AccessDenied)
+    id.vpath().resolve(root).ok_or(FileError::AccessDenied)
 }
 
 /// Read a file from disk.
@@ -623,13 +974,16 @@ impl<T: Clone> SlotCell<T> {
         if mem::replace(&mut self.accessed, true)
             && let Some(data) = &self.data
         {
-            println!("return data");
+            tracing::trace!("reusing data accessed in this compilation");
             return data.clone();
         }
 
         // Read and hash the file.
         let result = timed!("loading file", load());
-        println!("slot taken result {result:?}");
+        match &result {
+            Ok(data) => tracing::debug!(bytes = data.len(), "loaded file"),
+            Err(err) => tracing::debug!(%err, "failed to load file"),
+        }
         let fingerprint = timed!("hashing file", typst_utils::hash128(&result));
 
         // If the file contents didn't change, yield the old processed data.
@@ -713,6 +1067,10 @@ impl Document for HtmlDocument {
     }
 }
 
+/// How often the document is laid out at most while waiting for its
+/// introspections to stabilize.
+const MAX_LAYOUT_ITERATIONS: usize = 5;
+
 fn compile_impl<D: Document>(
     world: Tracked<dyn World + '_>,
     traced: Tracked<Traced>,
@@ -726,36 +1084,72 @@ fn compile_impl<D: Document>(
 
     // Fetch the main source file once.
     let main = world.main();
-    let main = world.source(main).unwrap();
+    let main = world
+        .source(main)
+        .map_err(|err| hint_invalid_main_file(err, main))?;
 
     // First evaluate the main source file into a module.
-    let content = typst_eval::eval(
-        &ROUTINES,
-        world,
-        traced,
-        sink.track_mut(),
-        Route::default().track(),
-        &main,
-    )?
-    .content();
-
+    let content = tracing::info_span!("eval")
+        .in_scope(|| {
+            typst_eval::eval(
+                &ROUTINES,
+                world,
+                traced,
+                sink.track_mut(),
+                Route::default().track(),
+                &main,
+            )
+        })?
+        .content();
+
+    let mut iter = 0;
     let mut subsink;
-    let introspector = &empty_introspector;
-
-    subsink = Sink::new();
-
-    let constraint = comemo::Constraint::new();
-    let mut engine = Engine {
-        world,
-        introspector: introspector.track_with(&constraint),
-        traced,
-        sink: subsink.track_mut(),
-        route: Route::default(),
-        routines: &ROUTINES,
-    };
+    let mut introspector = &empty_introspector;
+    let mut document: D;
+
+    // Relayout until all introspections stabilize.
+    // If that doesn't happen within `MAX_LAYOUT_ITERATIONS` attempts, we give up.
+    loop {
+        // The name of the iterations for timing scopes.
+        const ITER_NAMES: [&str; MAX_LAYOUT_ITERATIONS] = [
+            "layout (1)",
+            "layout (2)",
+            "layout (3)",
+            "layout (4)",
+            "layout (5)",
+        ];
+        let _scope = TimingScope::new(ITER_NAMES[iter]);
+        let _span = tracing::info_span!("layout", iteration = iter + 1).entered();
+
+        subsink = Sink::new();
+
+        let constraint = comemo::Constraint::new();
+        let mut engine = Engine {
+            world,
+            introspector: introspector.track_with(&constraint),
+            traced,
+            sink: subsink.track_mut(),
+            route: Route::default(),
+            routines: &ROUTINES,
+        };
 
-    // Layout!
-    let document = D::create(&mut engine, &content, styles)?;
+        // Layout!
+        document = D::create(&mut engine, &content, styles)?;
+        introspector = document.introspector();
+        iter += 1;
+
+        if timed!("check stabilized", constraint.validate(introspector)) {
+            break;
+        }
+
+        if iter >= MAX_LAYOUT_ITERATIONS {
+            subsink.warn(warning!(
+                Span::detached(), "layout did not converge within {} attempts", MAX_LAYOUT_ITERATIONS;
+                hint: "check if any states or queries are updating themselves"
+            ));
+            break;
+        }
+    }
 
     sink.extend_from_sink(subsink);
 
@@ -768,6 +1162,28 @@ fn compile_impl<D: Document>(
     Ok(document)
 }
 
+/// Adds useful hints when the main source file couldn't be read
+/// and returns the final diagnostic.
+fn hint_invalid_main_file(file_error: FileError, input: FileId) -> EcoVec<SourceDiagnostic> {
+    let is_utf8_error = matches!(file_error, FileError::InvalidUtf8);
+    let mut diagnostic = SourceDiagnostic::error(Span::detached(), EcoString::from(file_error));
+
+    // Attempt to provide helpful hints for UTF-8 errors. Perhaps the user
+    // mistyped the filename. For example, they could have written "file.pdf"
+    // instead of "file.typ".
+    if is_utf8_error {
+        let path = input.vpath();
+        let extension = path.as_rootless_path().extension();
+        if extension.is_some_and(|extension| extension == "typ") {
+            diagnostic.hint("the file could not be decoded as UTF-8");
+        } else {
+            diagnostic.hint("a Typst source file was expected");
+        }
+    }
+
+    eco_vec![diagnostic]
+}
+
 pub static ROUTINES: LazyLock<Routines> = LazyLock::new(|| Routines {
     rules: {
         let mut rules = NativeRuleMap::new();
@@ -783,66 +1199,6 @@ pub static ROUTINES: LazyLock<Routines> = LazyLock::new(|| Routines {
     html_span_filled: typst_html::html_span_filled,
 });
 
-#[derive(Debug, Clone)]
-pub enum Input {
-    /// Stdin, represented by `-`.
-    Stdin,
-    /// A non-empty path.
-    Path(PathBuf),
-}
-
-#[derive(Debug, Clone)]
-pub enum Output {
-    /// Stdout, represented by `-`.
-    Stdout,
-    /// A non-empty path.
-    Path(PathBuf),
-}
-
-/// A step-by-step writable version of [`Output`].
-#[derive(Debug)]
-pub enum OpenOutput<'a> {
-    Stdout(std::io::StdoutLock<'a>),
-    File(std::fs::File),
-}
-
-impl Output {
-    /// Write data to the output.
-    pub fn write(&self, buffer: &[u8]) -> std::io::Result<()> {
-        match self {
-            Output::Stdout => std::io::stdout().write_all(buffer),
-            Output::Path(path) => std::fs::write(path, buffer),
-        }
-    }
-
-    /// Open the output for writing.
-    pub fn open(&self) -> std::io::Result<OpenOutput<'_>> {
-        match self {
-            Self::Stdout => Ok(OpenOutput::Stdout(std::io::stdout().lock())),
-            Self::Path(path) => std::fs::File::create(path).map(OpenOutput::File),
-        }
-    }
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
-pub enum OutputFormat {
-    Pdf,
-    Png,
-    Svg,
-    Html,
-}
-
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
-pub enum DepsFormat {
-    /// Encodes as JSON, failing for non-Unicode paths.
-    #[default]
-    Json,
-    /// Separates paths with NULL bytes and can express all paths.
-    Zero,
-    /// Emits in Make format, omitting inexpressible paths.
-    Make,
-}
-
 /// A preprocessed `CompileCommand`.
 pub struct CompileConfig {
     /// Static warnings to emit after compilation.
@@ -857,9 +1213,14 @@ pub struct CompileConfig {
     pub output_format: OutputFormat,
     /// Which pages to export.
     pub pages: Option<PageRanges>,
+    /// The page ranges as given on the command line, to warn about the ones
+    /// beyond the end of the document.
+    pub page_args: Option<Vec<Pages>>,
     /// Opens the output file with the default viewer or a specific program after
     /// compilation.
     pub open: Option<Option<String>>,
+    /// The document's creation date formatted as a UNIX timestamp, with UTC suffix.
+    pub creation_timestamp: Option<DateTime<Utc>>,
     /// A list of standards the PDF should conform to.
     pub pdf_standards: PdfStandards,
     /// Whether to write PDF (accessibility) tags.
@@ -874,8 +1235,10 @@ pub struct CompileConfig {
 
 /// Export to HTML.
 fn export_html(document: &HtmlDocument, config: &CompileConfig) -> SourceResult<()> {
-    let html = typst_html::html(document)?;
-    let result = config.output.write(html.as_bytes());
+    let _span = tracing::info_span!("export", format = %config.output_format).entered();
+    let _scope = TimingScope::new("export");
+    let html = timed!("html", typst_html::html(document))?;
+    let result = timed!("write output", config.output.write(html.as_bytes()));
 
     result
         .map_err(|err| eco_format!("failed to write HTML file ({err})"))
@@ -904,7 +1267,7 @@ fn export_image(
             config
                 .pages
                 .as_ref()
-                .is_none_or(|exported_page_ranges| exported_page_ranges.includes_page_index(*i))
+                .is_none_or(|ranges| ranges.includes_page_index(*i))
         })
         .collect::<Vec<_>>();
 
@@ -983,17 +1346,14 @@ fn export_image_page(
     match fmt {
         ImageExportFormat::Png => {
             let pixmap = typst_render::render(page, config.ppi / 72.0);
-            let buf = pixmap
-                .encode_png()
+            let buf = timed!("encode png", pixmap.encode_png())
                 .map_err(|err| eco_format!("failed to encode PNG file ({err})"))?;
-            output
-                .write(&buf)
+            timed!("write output", output.write(&buf))
                 .map_err(|err| eco_format!("failed to write PNG file ({err})"))?;
         }
         ImageExportFormat::Svg => {
-            let svg = typst_svg::svg(page);
-            output
-                .write(svg.as_bytes())
+            let svg = timed!("svg", typst_svg::svg(page));
+            timed!("write output", output.write(svg.as_bytes()))
                 .map_err(|err| eco_format!("failed to write SVG file ({err})"))?;
         }
     }
@@ -1007,8 +1367,36 @@ enum ImageExportFormat {
     Svg,
 }
 
+/// Convert page ranges from the command line into the ones used by the
+/// exporters.
+fn page_ranges(pages: &[Pages]) -> PageRanges {
+    PageRanges::new(pages.iter().map(|r| r.0.clone()).collect())
+}
+
+/// Warn about page ranges that reach beyond the end of the document.
+fn check_page_ranges(pages: &[Pages], total: usize) -> impl Iterator<Item = SourceDiagnostic> {
+    pages
+        .iter()
+        .filter(move |range| {
+            [range.0.start(), range.0.end()]
+                .into_iter()
+                .flatten()
+                .any(|page| page.get() > total)
+        })
+        .map(move |range| {
+            warning!(
+                Span::detached(),
+                "page range {range} is out of bounds";
+                hint: "the document has {total} {}",
+                if total == 1 { "page" } else { "pages" }
+            )
+        })
+}
+
 /// Export to a paged target format.
 fn export_paged(document: &PagedDocument, config: &CompileConfig) -> SourceResult<Vec<Output>> {
+    let _span = tracing::info_span!("export", format = %config.output_format).entered();
+    let _scope = TimingScope::new("export");
     match config.output_format {
         OutputFormat::Pdf => export_pdf(document, config).map(|()| vec![config.output.clone()]),
         OutputFormat::Png => {
@@ -1023,18 +1411,158 @@ fn export_paged(document: &PagedDocument, config: &CompileConfig) -> SourceResul
 
 /// Export to a PDF.
 fn export_pdf(document: &PagedDocument, config: &CompileConfig) -> SourceResult<()> {
+    // If the timestamp is provided through the CLI, use UTC suffix,
+    // else, use the current local time and timezone.
+    let timestamp = match config.creation_timestamp {
+        Some(timestamp) => convert_datetime(timestamp).map(Timestamp::new_utc),
+        None => {
+            let local_datetime = Local::now();
+            convert_datetime(local_datetime).and_then(|datetime| {
+                Timestamp::new_local(datetime, local_datetime.offset().local_minus_utc() / 60)
+            })
+        }
+    };
+
     let options = PdfOptions {
         ident: Smart::Auto,
-        timestamp: None,
+        timestamp,
         page_ranges: config.pages.clone(),
         standards: config.pdf_standards.clone(),
         tagged: config.tagged,
     };
     let buffer = typst_pdf::pdf(document, &options)?;
-    config
-        .output
-        .write(&buffer)
+    timed!("write output", config.output.write(&buffer))
         .map_err(|err| eco_format!("failed to write PDF file ({err})"))
         .at(Span::detached())?;
     Ok(())
 }
+
+impl From<PdfStandard> for typst_pdf::PdfStandard {
+    fn from(standard: PdfStandard) -> Self {
+        match standard {
+            PdfStandard::V_1_4 => typst_pdf::PdfStandard::V_1_4,
+            PdfStandard::V_1_5 => typst_pdf::PdfStandard::V_1_5,
+            PdfStandard::V_1_6 => typst_pdf::PdfStandard::V_1_6,
+            PdfStandard::V_1_7 => typst_pdf::PdfStandard::V_1_7,
+            PdfStandard::V_2_0 => typst_pdf::PdfStandard::V_2_0,
+            PdfStandard::A_1b => typst_pdf::PdfStandard::A_1b,
+            PdfStandard::A_1a => typst_pdf::PdfStandard::A_1a,
+            PdfStandard::A_2b => typst_pdf::PdfStandard::A_2b,
+            PdfStandard::A_2u => typst_pdf::PdfStandard::A_2u,
+            PdfStandard::A_2a => typst_pdf::PdfStandard::A_2a,
+            PdfStandard::A_3b => typst_pdf::PdfStandard::A_3b,
+            PdfStandard::A_3u => typst_pdf::PdfStandard::A_3u,
+            PdfStandard::A_3a => typst_pdf::PdfStandard::A_3a,
+            PdfStandard::A_4 => typst_pdf::PdfStandard::A_4,
+            PdfStandard::A_4f => typst_pdf::PdfStandard::A_4f,
+            PdfStandard::A_4e => typst_pdf::PdfStandard::A_4e,
+            PdfStandard::UA_1 => typst_pdf::PdfStandard::Ua_1,
+        }
+    }
+}
+
+/// Convert [`chrono::DateTime`] to [`Datetime`]
+fn convert_datetime<Tz: chrono::TimeZone>(date_time: chrono::DateTime<Tz>) -> Option<Datetime> {
+    Datetime::from_ymd_hms(
+        date_time.year(),
+        date_time.month().try_into().ok()?,
+        date_time.day().try_into().ok()?,
+        date_time.hour().try_into().ok()?,
+        date_time.minute().try_into().ok()?,
+        date_time.second().try_into().ok()?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::CompileCommand;
+
+    fn check(pages: &[&str], total: usize) -> Vec<(String, Vec<String>)> {
+        let pages: Vec<Pages> = pages.iter().map(|s| s.parse().unwrap()).collect();
+        check_page_ranges(&pages, total)
+            .map(|warning| {
+                let hints = warning.hints.iter().map(ToString::to_string).collect();
+                (warning.message.to_string(), hints)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_page_ranges_within_the_document() {
+        assert!(check(&["1", "2-3", "-3", "2-"], 3).is_empty());
+        assert!(check(&["1-", "-1"], 1).is_empty());
+    }
+
+    #[test]
+    fn test_page_ranges_out_of_bounds() {
+        assert_eq!(
+            check(&["1", "2-5", "4-", "-9"], 3),
+            [
+                (
+                    "page range 2-5 is out of bounds",
+                    "the document has 3 pages"
+                ),
+                ("page range 4- is out of bounds", "the document has 3 pages"),
+                ("page range -9 is out of bounds", "the document has 3 pages"),
+            ]
+            .map(|(message, hint)| (message.to_string(), vec![hint.to_string()]))
+        );
+        assert_eq!(
+            check(&["2"], 1),
+            [(
+                "page range 2 is out of bounds".to_string(),
+                vec!["the document has 1 page".to_string()]
+            )]
+        );
+    }
+
+    const MAIN: &str = "#import \"lib.typ\": greeting\n= #greeting\n";
+
+    /// A renderer for the file that only uses the embedded fonts.
+    pub(crate) fn renderer(input: &Path) -> Renderer {
+        let command = CompileCommand::try_parse_from([
+            "compile".as_ref(),
+            input.as_os_str(),
+            "--ignore-system-fonts".as_ref(),
+        ])
+        .unwrap();
+        let args = &command.args;
+        Renderer::new(&args.input, &args.world, &args.process).unwrap()
+    }
+
+    /// A renderer for a project whose main file imports another one.
+    fn project() -> (tempfile::TempDir, Renderer) {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("main.typ");
+        fs::write(&input, MAIN).unwrap();
+        fs::write(dir.path().join("lib.typ"), "#let greeting = [Hello]\n").unwrap();
+        let renderer = renderer(&input);
+        (dir, renderer)
+    }
+
+    #[test]
+    fn test_render_from_string_keeps_the_main_file() {
+        let (dir, mut renderer) = project();
+        let main = renderer.world.main();
+
+        // The import is resolved next to the main file, and errors are
+        // reported in it.
+        let text = "#import \"lib.typ\": greeting\n#greeting #missing\n";
+        let errors = renderer
+            .render_from_string(text.into(), None)
+            .output
+            .unwrap_err();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].message.contains("unknown variable"));
+        assert_eq!(errors[0].span.id(), Some(main));
+        assert_eq!(renderer.world.main(), main);
+        assert_eq!(renderer.world.source(main).unwrap().text(), text);
+
+        // The file is read from disk again once it is compiled from there.
+        renderer.world.reset();
+        let path = dir.path().join("main.typ");
+        assert!(renderer.render_from_path(&path, None).output.is_ok());
+        assert_eq!(renderer.world.source(main).unwrap().text(), MAIN);
+    }
+}
@@ -0,0 +1,550 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ecow::eco_format;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use typst_kit::download::Downloader;
+use typst_kit::package::{
+    DEFAULT_NAMESPACE, DEFAULT_REGISTRY, default_package_cache_path, default_package_path,
+};
+use typst_library::diag::{PackageError, PackageResult, StrResult, bail};
+use typst_syntax::package::{PackageManifest, PackageSpec, PackageVersion};
+
+use crate::args::PackageArgs;
+use crate::download::{self, PrintDownload};
+
+/// Makes packages that are not available on disk yet available.
+///
+/// The storage takes care of placing the fetched files at their final
+/// location, so implementors only need to produce the package's contents.
+pub trait PackageFetcher: Send + Sync {
+    /// Write the files of the package into `dest`, an empty directory.
+    fn fetch(&self, spec: &PackageSpec, dest: &Path) -> PackageResult<()>;
+}
+
+/// Holds information about where packages are stored and fetches them on
+/// demand, if possible.
+pub struct PackageStorage {
+    /// The path at which local packages are stored.
+    package_path: Option<PathBuf>,
+    /// The path at which fetched packages are stored.
+    package_cache_path: Option<PathBuf>,
+    /// Fetches packages that are neither local nor cached.
+    fetcher: Box<dyn PackageFetcher>,
+}
+
+impl PackageStorage {
+    /// Creates a new package storage for the given args. Packages are
+    /// fetched from the configured registry directory, if any, and from the
+    /// network otherwise.
+    pub fn new(args: &PackageArgs) -> Self {
+        let fetcher: Box<dyn PackageFetcher> = match &args.package_registry {
+            Some(dir) => Box::new(DirectoryFetcher::new(dir.clone())),
+            None => Box::new(NetworkFetcher::new(download::downloader())),
+        };
+
+        Self::with_fetcher(
+            args.package_path.clone(),
+            args.package_cache_path.clone(),
+            fetcher,
+        )
+    }
+
+    /// Creates a new package storage with a custom fetcher. Falls back to the
+    /// recommended XDG directories if the paths are `None`.
+    pub fn with_fetcher(
+        package_path: Option<PathBuf>,
+        package_cache_path: Option<PathBuf>,
+        fetcher: Box<dyn PackageFetcher>,
+    ) -> Self {
+        Self {
+            package_path: package_path.or_else(default_package_path),
+            package_cache_path: package_cache_path.or_else(default_package_cache_path),
+            fetcher,
+        }
+    }
+
+    /// Makes a package available on-disk and returns the path at which it is
+    /// located (will be either in the package or cache directory).
+    ///
+    /// The manifest is validated wherever the package is found, so that a
+    /// broken local package is reported just like a broken fetched one.
+    pub fn prepare_package(&self, spec: &PackageSpec) -> PackageResult<PathBuf> {
+        let subdir = package_subdir(spec);
+
+        if let Some(packages_dir) = &self.package_path {
+            let dir = packages_dir.join(&subdir);
+            if dir.exists() {
+                validate_manifest(spec, &dir)?;
+                return Ok(dir);
+            }
+        }
+
+        if let Some(cache_dir) = &self.package_cache_path {
+            let dir = cache_dir.join(&subdir);
+            if dir.exists() {
+                validate_manifest(spec, &dir)?;
+                return Ok(dir);
+            }
+
+            // Local packages are never fetched, they must be placed into the
+            // package directory by hand.
+            if spec.namespace != "local" {
+                self.fetch_package(spec, &dir)?;
+                return Ok(dir);
+            }
+        }
+
+        Err(PackageError::NotFound(spec.clone()))
+    }
+
+    /// Fetch a package into the given directory of the cache.
+    fn fetch_package(&self, spec: &PackageSpec, package_dir: &Path) -> PackageResult<()> {
+        // The directory in which the package's versions live.
+        let base_dir = package_dir.parent().unwrap();
+
+        // To prevent multiple instances from interfering, we fetch into a
+        // temporary directory first and then move this directory to its final
+        // destination. By locating the temporary directory directly next to
+        // where the package directory will live, we are (trying our best)
+        // making sure that both are on the same mount point, which is
+        // required for renaming.
+        let tempname = format!(".tmp-{}-{}", spec.version, fastrand::u32(..));
+        let tempdir = Tempdir::create(base_dir.join(tempname))
+            .map_err(|err| error("failed to create temporary package directory", err))?;
+
+        self.fetcher.fetch(spec, tempdir.as_ref())?;
+
+        // Don't let a broken package into the cache, where it would stay
+        // until removed by hand.
+        validate_manifest(spec, tempdir.as_ref())?;
+
+        // If another instance already moved the package into place, the
+        // target directory isn't empty and we can use that one.
+        match fs::rename(&tempdir, package_dir) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::DirectoryNotEmpty => Ok(()),
+            Err(err) => Err(error("failed to move fetched package directory", err)),
+        }
+    }
+}
+
+/// Fetches packages from the Typst package registry over the network.
+///
+/// Only packages in the public `preview` namespace are available there.
+pub struct NetworkFetcher {
+    /// The downloader used for fetching the index and packages.
+    downloader: Downloader,
+    /// The cached index of the default namespace.
+    index: OnceCell<Vec<serde_json::Value>>,
+}
+
+impl NetworkFetcher {
+    /// Creates a new fetcher that downloads with the given downloader.
+    pub fn new(downloader: Downloader) -> Self {
+        Self {
+            downloader,
+            index: OnceCell::new(),
+        }
+    }
+
+    /// Download the package index. The result of this is cached for
+    /// efficiency.
+    fn download_index(&self) -> StrResult<&[serde_json::Value]> {
+        self.index
+            .get_or_try_init(|| {
+                let url = format!("{DEFAULT_REGISTRY}/{DEFAULT_NAMESPACE}/index.json");
+                match self.downloader.download(&url) {
+                    Ok(response) => response
+                        .into_json()
+                        .map_err(|err| eco_format!("failed to parse package index: {err}")),
+                    Err(ureq::Error::Status(404, _)) => {
+                        bail!("failed to fetch package index (not found)")
+                    }
+                    Err(err) => bail!("failed to fetch package index ({err})"),
+                }
+            })
+            .map(AsRef::as_ref)
+    }
+
+    /// Tries to determine the latest version of a package.
+    fn latest_version(&self, spec: &PackageSpec) -> Option<PackageVersion> {
+        /// Minimal information required about a package to determine its
+        /// latest version.
+        #[derive(Deserialize)]
+        struct MinimalPackageInfo {
+            name: String,
+            version: PackageVersion,
+        }
+
+        self.download_index()
+            .ok()?
+            .iter()
+            .filter_map(|value| MinimalPackageInfo::deserialize(value).ok())
+            .filter(|package| package.name == spec.name)
+            .map(|package| package.version)
+            .max()
+    }
+}
+
+impl PackageFetcher for NetworkFetcher {
+    fn fetch(&self, spec: &PackageSpec, dest: &Path) -> PackageResult<()> {
+        if spec.namespace != DEFAULT_NAMESPACE {
+            return Err(PackageError::NotFound(spec.clone()));
+        }
+
+        let url = format!(
+            "{DEFAULT_REGISTRY}/{DEFAULT_NAMESPACE}/{}-{}.tar.gz",
+            spec.name, spec.version
+        );
+
+        let data = match self
+            .downloader
+            .download_with_progress(&url, &mut PrintDownload(spec))
+        {
+            Ok(data) => data,
+            Err(ureq::Error::Status(404, _)) => {
+                return Err(not_found(spec, self.latest_version(spec)));
+            }
+            Err(err) => {
+                return Err(PackageError::NetworkFailed(Some(eco_format!("{err}"))));
+            }
+        };
+
+        let decompressed = flate2::read::GzDecoder::new(data.as_slice());
+        tar::Archive::new(decompressed)
+            .unpack(dest)
+            .map_err(|err| PackageError::MalformedArchive(Some(eco_format!("{err}"))))
+    }
+}
+
+/// Fetches packages from a directory that is laid out like a package path,
+/// i.e. with packages at `{namespace}/{name}/{version}`.
+///
+/// This makes it possible to use a registry without network access, for
+/// example a mirror or a set of test fixtures.
+pub struct DirectoryFetcher {
+    /// The registry's root directory.
+    root: PathBuf,
+}
+
+impl DirectoryFetcher {
+    /// Creates a new fetcher that copies packages out of the given directory.
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Determines the latest version of a package in the registry.
+    fn latest_version(&self, spec: &PackageSpec) -> Option<PackageVersion> {
+        fs::read_dir(self.root.join(&*spec.namespace).join(&*spec.name))
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+            .max()
+    }
+}
+
+impl PackageFetcher for DirectoryFetcher {
+    fn fetch(&self, spec: &PackageSpec, dest: &Path) -> PackageResult<()> {
+        let src = self.root.join(package_subdir(spec));
+        if !src.is_dir() {
+            return Err(not_found(spec, self.latest_version(spec)));
+        }
+
+        copy_dir(&src, dest).map_err(|err| error("failed to copy package", err))
+    }
+}
+
+/// Ensure that a package directory contains a well-formed manifest that
+/// matches the spec.
+fn validate_manifest(spec: &PackageSpec, dir: &Path) -> PackageResult<()> {
+    let malformed = |message| PackageError::Other(Some(message));
+
+    let path = dir.join("typst.toml");
+    let string = match fs::read_to_string(&path) {
+        Ok(string) => string,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(malformed(
+                "package does not contain a typst.toml manifest".into(),
+            ));
+        }
+        Err(err) => return Err(error("failed to read package manifest", err)),
+    };
+
+    let manifest: PackageManifest = toml::from_str(&string).map_err(|err| {
+        malformed(eco_format!(
+            "package manifest is malformed ({})",
+            err.message()
+        ))
+    })?;
+    manifest.validate(spec).map_err(malformed)?;
+
+    if !dir.join(&*manifest.package.entrypoint).is_file() {
+        return Err(malformed(eco_format!(
+            "package entrypoint `{}` does not exist",
+            manifest.package.entrypoint
+        )));
+    }
+
+    Ok(())
+}
+
+/// The directory of a package relative to a package path.
+fn package_subdir(spec: &PackageSpec) -> String {
+    format!("{}/{}/{}", spec.namespace, spec.name, spec.version)
+}
+
+/// The error for a missing package, pointing to the latest available version
+/// if there is one.
+fn not_found(spec: &PackageSpec, latest: Option<PackageVersion>) -> PackageError {
+    match latest {
+        Some(version) => PackageError::VersionNotFound(spec.clone(), version),
+        None => PackageError::NotFound(spec.clone()),
+    }
+}
+
+/// Recursively copy the contents of a directory into another one.
+fn copy_dir(src: &Path, dest: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            fs::create_dir(&target)?;
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// A temporary directory that is automatically cleaned up.
+struct Tempdir(PathBuf);
+
+impl Tempdir {
+    /// Creates a directory at the path and auto-cleans it.
+    fn create(path: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&path)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for Tempdir {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+impl AsRef<Path> for Tempdir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+/// Enriches an I/O error with a message and turns it into a
+/// `PackageError::Other`.
+#[cold]
+fn error(message: &str, err: io::Error) -> PackageError {
+    PackageError::Other(Some(eco_format!("{message}: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "\
+[package]
+name = \"example\"
+version = \"0.1.0\"
+entrypoint = \"lib.typ\"
+";
+
+    /// A package storage with empty package and cache directories that
+    /// fetches from a registry directory.
+    struct Registry {
+        dir: tempfile::TempDir,
+        storage: PackageStorage,
+    }
+
+    impl Registry {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let storage = PackageStorage::with_fetcher(
+                Some(dir.path().join("packages")),
+                Some(dir.path().join("cache")),
+                Box::new(DirectoryFetcher::new(dir.path().join("registry"))),
+            );
+            Self { dir, storage }
+        }
+
+        /// Add a package with the files to the registry or the package
+        /// directory.
+        fn add(&self, base: &str, spec: &str, files: &[(&str, &str)]) {
+            let dir = self
+                .dir
+                .path()
+                .join(base)
+                .join(package_subdir(&parse(spec)));
+            fs::create_dir_all(&dir).unwrap();
+            for (name, contents) in files {
+                fs::write(dir.join(name), contents).unwrap();
+            }
+        }
+
+        fn prepare(&self, spec: &str) -> PackageResult<PathBuf> {
+            self.storage.prepare_package(&parse(spec))
+        }
+
+        /// Whether anything was put into the cache.
+        fn cached(&self) -> bool {
+            self.dir
+                .path()
+                .join("cache/preview/example")
+                .read_dir()
+                .is_ok_and(|mut dir| dir.next().is_some())
+        }
+    }
+
+    fn parse(spec: &str) -> PackageSpec {
+        spec.parse().unwrap()
+    }
+
+    #[track_caller]
+    fn assert_malformed(result: PackageResult<PathBuf>, message: &str) {
+        match result {
+            Err(PackageError::Other(Some(error))) => {
+                assert!(error.contains(message), "unexpected error: {error}")
+            }
+            other => panic!("expected an error containing {message:?}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_fetch_from_directory() {
+        let registry = Registry::new();
+        registry.add(
+            "registry",
+            "@preview/example:0.1.0",
+            &[("typst.toml", MANIFEST), ("lib.typ", "#let x = 1")],
+        );
+        let dir = registry.prepare("@preview/example:0.1.0").unwrap();
+        assert_eq!(dir, registry.dir.path().join("cache/preview/example/0.1.0"));
+        assert_eq!(
+            fs::read_to_string(dir.join("lib.typ")).unwrap(),
+            "#let x = 1"
+        );
+
+        // The second time, the package is found in the cache.
+        fs::remove_dir_all(registry.dir.path().join("registry")).unwrap();
+        assert_eq!(registry.prepare("@preview/example:0.1.0").unwrap(), dir);
+    }
+
+    #[test]
+    fn test_fetch_missing_package() {
+        let registry = Registry::new();
+        assert_eq!(
+            registry.prepare("@preview/example:0.1.0"),
+            Err(PackageError::NotFound(parse("@preview/example:0.1.0")))
+        );
+    }
+
+    #[test]
+    fn test_fetch_missing_version() {
+        let registry = Registry::new();
+        for version in ["0.2.0", "0.10.0"] {
+            let manifest = MANIFEST.replace("0.1.0", version);
+            registry.add(
+                "registry",
+                &format!("@preview/example:{version}"),
+                &[("typst.toml", &manifest), ("lib.typ", "")],
+            );
+        }
+        assert_eq!(
+            registry.prepare("@preview/example:0.1.0"),
+            Err(PackageError::VersionNotFound(
+                parse("@preview/example:0.1.0"),
+                "0.10.0".parse().unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_fetch_without_manifest() {
+        let registry = Registry::new();
+        registry.add("registry", "@preview/example:0.1.0", &[("lib.typ", "")]);
+        assert_malformed(
+            registry.prepare("@preview/example:0.1.0"),
+            "does not contain a typst.toml manifest",
+        );
+        assert!(!registry.cached());
+    }
+
+    #[test]
+    fn test_fetch_malformed_manifest() {
+        let registry = Registry::new();
+        registry.add(
+            "registry",
+            "@preview/example:0.1.0",
+            &[("typst.toml", "[package]\nname = "), ("lib.typ", "")],
+        );
+        assert_malformed(
+            registry.prepare("@preview/example:0.1.0"),
+            "package manifest is malformed",
+        );
+        assert!(!registry.cached());
+    }
+
+    #[test]
+    fn test_fetch_mismatched_manifest() {
+        let registry = Registry::new();
+        let files = [("typst.toml", MANIFEST), ("lib.typ", "")];
+        registry.add("registry", "@preview/other:0.1.0", &files);
+        registry.add("registry", "@preview/example:0.2.0", &files);
+        assert_malformed(
+            registry.prepare("@preview/other:0.1.0"),
+            "mismatched name `example`",
+        );
+        assert_malformed(
+            registry.prepare("@preview/example:0.2.0"),
+            "mismatched version 0.1.0",
+        );
+        assert!(!registry.cached());
+    }
+
+    #[test]
+    fn test_fetch_missing_entrypoint() {
+        let registry = Registry::new();
+        registry.add(
+            "registry",
+            "@preview/example:0.1.0",
+            &[("typst.toml", MANIFEST)],
+        );
+        assert_malformed(
+            registry.prepare("@preview/example:0.1.0"),
+            "entrypoint `lib.typ` does not exist",
+        );
+        assert!(!registry.cached());
+    }
+
+    #[test]
+    fn test_validate_local_package() {
+        let registry = Registry::new();
+        registry.add("packages", "@local/example:0.1.0", &[("lib.typ", "")]);
+        assert_malformed(
+            registry.prepare("@local/example:0.1.0"),
+            "does not contain a typst.toml manifest",
+        );
+
+        registry.add(
+            "packages",
+            "@local/example:0.1.0",
+            &[("typst.toml", MANIFEST)],
+        );
+        assert_eq!(
+            registry.prepare("@local/example:0.1.0").unwrap(),
+            registry.dir.path().join("packages/local/example/0.1.0")
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use comemo::Track;
+use ecow::{EcoString, eco_format};
+use serde::Serialize;
+use typst_library::World;
+use typst_library::diag::{HintedStrResult, StrResult, Warned, bail};
+use typst_library::engine::Sink;
+use typst_library::foundations::{Content, IntoValue, LocatableSelector, Repr, Scope, Value};
+use typst_library::introspection::Introspector;
+use typst_library::layout::PagedDocument;
+use typst_syntax::{Span, SyntaxMode};
+
+use crate::args::{QueryCommand, QueryFormat};
+use crate::{Document, ROUTINES, Renderer, compile, set_failed};
+
+/// Execute a query command.
+pub fn query(command: &QueryCommand) -> HintedStrResult<()> {
+    let renderer = Renderer::new(&command.input, &command.world, &command.process)?;
+
+    let Warned { output, warnings } = compile::<PagedDocument>(&renderer.world);
+    match output {
+        // Retrieve and print the matches, then warn about anything that
+        // might have made them incomplete.
+        Ok(document) => {
+            let elements = retrieve(&renderer.world, &command.selector, document.introspector())?;
+            let formatted = format(elements, command)?;
+            println!("{formatted}");
+            renderer.report(&[], &warnings)?;
+        }
+        Err(errors) => {
+            set_failed();
+            renderer.report(&errors, &warnings)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Retrieve the matches for the selector.
+fn retrieve(
+    world: &dyn World,
+    selector: &str,
+    introspector: &Introspector,
+) -> StrResult<Vec<Content>> {
+    let selector = typst_eval::eval_string(
+        &ROUTINES,
+        world.track(),
+        Sink::new().track_mut(),
+        selector,
+        Span::detached(),
+        SyntaxMode::Code,
+        Scope::default(),
+    )
+    .map_err(|errors| {
+        let mut message = EcoString::from("failed to evaluate selector");
+        for (i, error) in errors.into_iter().enumerate() {
+            message.push_str(if i == 0 { ": " } else { ", " });
+            message.push_str(&error.message);
+        }
+        message
+    })?
+    .cast::<LocatableSelector>()
+    .map_err(|err| err.message().clone())?;
+
+    Ok(introspector.query(&selector.0).into_iter().collect())
+}
+
+/// Format the matches, or the requested field of them, as the command asks.
+fn format(elements: Vec<Content>, command: &QueryCommand) -> StrResult<String> {
+    if command.one && elements.len() != 1 {
+        bail!("expected exactly one element, found {}", elements.len());
+    }
+
+    let values: Vec<Value> = elements
+        .into_iter()
+        .filter_map(|element| match &command.field {
+            Some(field) => element.get_by_name(field).ok(),
+            None => Some(element.into_value()),
+        })
+        .collect();
+
+    if command.one && values.is_empty() {
+        bail!(
+            "the element has no field `{}`",
+            command.field.as_deref().unwrap_or_default()
+        );
+    }
+
+    // With `--one`, the value is printed on its own instead of in a list.
+    match command.format {
+        QueryFormat::Json if command.one => json(&values[0]),
+        QueryFormat::Json => json(&values),
+        QueryFormat::Yaml if command.one => yaml(&values[0]),
+        QueryFormat::Yaml => yaml(&values),
+        QueryFormat::Plain => Ok(values.iter().map(plain).collect::<Vec<_>>().join("\n")),
+    }
+}
+
+/// Serialize data as pretty-printed JSON.
+fn json(data: &impl Serialize) -> StrResult<String> {
+    serde_json::to_string_pretty(data).map_err(|e| eco_format!("{e}"))
+}
+
+/// Serialize data as YAML, without the trailing newline.
+fn yaml(data: &impl Serialize) -> StrResult<String> {
+    serde_yaml::to_string(data)
+        .map(|yaml| yaml.trim_end().to_owned())
+        .map_err(|e| eco_format!("{e}"))
+}
+
+/// Format a value as plain text.
+fn plain(value: &Value) -> String {
+    match value {
+        Value::Str(string) => string.as_str().to_owned(),
+        Value::Content(content) => content.plain_text().into(),
+        value => value.repr().into(),
+    }
+}
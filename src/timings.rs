@@ -0,0 +1,103 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use typst_library::World;
+use typst_library::diag::HintedStrResult;
+use typst_syntax::Span;
+
+use crate::args::{CliArguments, Command};
+use crate::{Renderer, SystemWorld};
+
+/// Records the timings of the scopes that are instrumented with
+/// `typst_timing` and writes them to a Chrome trace file.
+///
+/// In watch mode, every compilation is recorded into a file of its own. The
+/// path must then contain `{n}`, which is replaced with the number of the
+/// compilation, starting at zero.
+pub struct Timer {
+    /// Where to save the recorded timings, if they are recorded at all.
+    path: Option<PathBuf>,
+    /// The number of the next recording.
+    index: usize,
+    /// The file that the last recording was written to.
+    recorded: Option<PathBuf>,
+}
+
+impl Timer {
+    /// Enable the collection of timings if the command asks for them.
+    pub fn new(args: &CliArguments) -> Self {
+        let path = match &args.command {
+            Command::Compile(command) | Command::Image(command) | Command::Html(command) => {
+                command.args.timings.clone()
+            }
+            Command::Watch(command) => command.args.timings.clone(),
+            _ => None,
+        };
+
+        if path.is_some() {
+            typst_timing::enable();
+        }
+
+        Self {
+            path,
+            index: 0,
+            recorded: None,
+        }
+    }
+
+    /// Record the timings of everything `f` does with the renderer and write
+    /// them to disk.
+    pub fn record<T>(
+        &mut self,
+        renderer: &mut Renderer,
+        f: impl FnOnce(&mut Renderer) -> T,
+    ) -> HintedStrResult<T> {
+        let Some(path) = &self.path else {
+            return Ok(f(renderer));
+        };
+
+        typst_timing::clear();
+
+        // A path without `{n}` is rejected in watch mode when the compilation
+        // is configured, so it is only ever written once.
+        let string = path.to_string_lossy();
+        let numbered = string.contains("{n}");
+        debug_assert!(numbered || self.index == 0);
+
+        let storage;
+        let path = if numbered {
+            storage = string.replace("{n}", &self.index.to_string());
+            Path::new(&storage)
+        } else {
+            path.as_path()
+        };
+
+        let output = f(renderer);
+        self.index += 1;
+
+        let file = File::create(path).map_err(|err| format!("failed to create file ({err})"))?;
+        let writer = BufWriter::with_capacity(1 << 20, file);
+        typst_timing::export_json(writer, |span| {
+            resolve_span(&renderer.world, Span::from_raw(span))
+                .unwrap_or_else(|| ("unknown".to_string(), 0))
+        })?;
+
+        self.recorded = Some(path.to_path_buf());
+        Ok(output)
+    }
+
+    /// The file that the last recording was written to, if any.
+    pub fn recorded(&self) -> Option<&Path> {
+        self.recorded.as_deref()
+    }
+}
+
+/// Turn a span into a file and line pair.
+fn resolve_span(world: &SystemWorld, span: Span) -> Option<(String, u32)> {
+    let id = span.id()?;
+    let source = world.source(id).ok()?;
+    let range = source.range(span)?;
+    let line = source.lines().byte_to_line(range.start)?;
+    Some((format!("{id:?}"), line as u32 + 1))
+}
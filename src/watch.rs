@@ -0,0 +1,462 @@
+use std::io::{self, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use codespan_reporting::term::termcolor::WriteColor;
+use codespan_reporting::term::{self, termcolor};
+use ecow::eco_format;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher as _};
+use rustc_hash::{FxHashMap, FxHashSet};
+use same_file::is_same_file;
+use typst_library::diag::{HintedStrResult, HintedString, StrResult, warning};
+use typst_syntax::Span;
+use typst_utils::format_duration;
+
+use crate::args::{CompileArgs, Input, Output};
+use crate::timings::Timer;
+use crate::{
+    CompileConfig, Renderer, SystemWorld, WorldCreationError, compile_once, print_error,
+    print_hint, terminal,
+};
+
+/// Execute a watching compilation command.
+pub fn watch(args: &CompileArgs, timer: &mut Timer) -> HintedStrResult<()> {
+    let config = Renderer::compile_config(args, None, true)?;
+
+    // Create a file system watcher.
+    let mut watcher = Watcher::new()?;
+
+    // Create the world that serves sources, files, and fonts.
+    // Additionally, if any files do not exist, wait until they do.
+    let mut renderer = loop {
+        match SystemWorld::new(&args.input, &args.world, &args.process) {
+            Ok(world) => {
+                break Renderer {
+                    world,
+                    diagnostic_format: args.process.diagnostic_format,
+                };
+            }
+            Err(
+                ref err @ (WorldCreationError::InputNotFound(ref path)
+                | WorldCreationError::RootNotFound(ref path)),
+            ) => {
+                watcher.update([path.clone()], [])?;
+                Status::Error.print(&config).unwrap();
+                print_error(&err.to_string()).unwrap();
+                watcher.wait()?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    // Perform initial compilation.
+    let outputs = timer.record(&mut renderer, |renderer| compile_once(renderer, &config))??;
+    watcher.ignore(&outputs, config.deps.as_ref(), timer.recorded());
+
+    // Print warning when trying to watch stdin.
+    if matches!(&config.input, Input::Stdin) {
+        warn_watching_std(&renderer)?;
+    }
+
+    loop {
+        // Watch all dependencies of the most recent compilation, the project
+        // root (to notice newly created files) and the custom font
+        // directories.
+        let root = renderer.world.root().to_path_buf();
+        let font_paths = renderer.world.font_paths().to_vec();
+        watcher.update(
+            renderer.world.dependencies().chain(iter::once(root)),
+            font_paths.iter().cloned(),
+        )?;
+
+        // Wait until anything relevant happens.
+        let changed = watcher.wait()?;
+
+        if changed
+            .iter()
+            .any(|path| font_paths.iter().any(|dir| path.starts_with(dir)))
+        {
+            // Fonts are only searched when the world is created, so start
+            // over with a fresh one. If the fonts cannot be found, e.g.
+            // because they no longer match the lockfile, the error is
+            // reported like a failed compilation and the old world is kept
+            // until the fonts change again.
+            match SystemWorld::new(&args.input, &args.world, &args.process) {
+                Ok(world) => renderer.world = world,
+                Err(err) => {
+                    let err = HintedString::from(err);
+                    Status::Error.print(&config).unwrap();
+                    print_error(err.message()).unwrap();
+                    for hint in err.hints() {
+                        print_hint(hint).unwrap();
+                    }
+                    continue;
+                }
+            }
+        } else {
+            // Reset all dependencies. Files whose contents did not change
+            // keep their fingerprint and are not parsed again.
+            renderer.world.reset();
+        }
+
+        // Recompile.
+        let outputs = timer.record(&mut renderer, |renderer| compile_once(renderer, &config))??;
+        watcher.ignore(&outputs, config.deps.as_ref(), timer.recorded());
+
+        // Evict the cache.
+        comemo::evict(10);
+    }
+}
+
+/// Watches file system activity.
+struct Watcher {
+    /// The output files. We ignore any events for them.
+    outputs: Vec<PathBuf>,
+    /// The dependency file. Events for it are ignored, too.
+    deps: Option<PathBuf>,
+    /// The timings of the last compilation. Events for it are ignored, too.
+    recording: Option<PathBuf>,
+    /// The underlying watcher.
+    watcher: RecommendedWatcher,
+    /// Notify event receiver.
+    rx: Receiver<notify::Result<Event>>,
+    /// Keeps track of which paths are watched via `watcher`. The boolean is
+    /// used during updating for mark-and-sweep garbage collection of paths we
+    /// should unwatch.
+    watched: FxHashMap<PathBuf, bool>,
+    /// A set of files that should be watched, but don't exist. We manually poll
+    /// for those.
+    missing: FxHashSet<PathBuf>,
+    /// The directories that are watched recursively. Changes to the contents
+    /// of files inside of them are relevant, whereas for non-recursively
+    /// watched directories (like the project root) only newly created or
+    /// removed entries are.
+    dirs: Vec<PathBuf>,
+}
+
+impl Watcher {
+    /// How long to wait for a shortly following file system event when
+    /// watching.
+    const BATCH_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// The maximum time we spend batching events before quitting wait().
+    const STARVE_TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// The interval in which we poll when falling back to poll watching
+    /// due to missing files.
+    const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+    /// Create a new, blank watcher.
+    fn new() -> StrResult<Self> {
+        // Setup file watching.
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        // Set the poll interval to something more eager than the default. That
+        // default seems a bit excessive for our purposes at around 30s.
+        // Note that this only affects a tiny number of systems. Most do not
+        // use the [`notify::PollWatcher`].
+        let config = notify::Config::default().with_poll_interval(Self::POLL_INTERVAL);
+        let watcher = RecommendedWatcher::new(tx, config)
+            .map_err(|err| eco_format!("failed to setup file watching ({err})"))?;
+
+        Ok(Self {
+            outputs: Vec::new(),
+            deps: None,
+            recording: None,
+            rx,
+            watcher,
+            watched: FxHashMap::default(),
+            missing: FxHashSet::default(),
+            dirs: Vec::new(),
+        })
+    }
+
+    /// Ignore events for the given outputs of the last compilation, the
+    /// dependency file and the recorded timings.
+    fn ignore(&mut self, outputs: &[Output], deps: Option<&Output>, recording: Option<&Path>) {
+        let path = |output: &Output| match output {
+            Output::Path(path) => Some(resolve(path)),
+            Output::Stdout => None,
+        };
+
+        // A failed compilation leaves the outputs of the last successful one
+        // in place. The dependencies and timings are written regardless.
+        if !outputs.is_empty() {
+            self.outputs = outputs.iter().filter_map(path).collect();
+        }
+        self.deps = deps.and_then(path);
+        self.recording = recording.map(resolve);
+    }
+
+    /// Update the watching to watch exactly the listed files and,
+    /// recursively, directories.
+    ///
+    /// Paths that are not yet watched will be watched. Paths that are already
+    /// watched, but don't need to be watched anymore, will be unwatched.
+    fn update(
+        &mut self,
+        files: impl IntoIterator<Item = PathBuf>,
+        dirs: impl IntoIterator<Item = PathBuf>,
+    ) -> StrResult<()> {
+        // Mark all paths as not "seen" so that we may unwatch them if they
+        // aren't in the dependency list.
+        #[allow(clippy::iter_over_hash_type, reason = "order does not matter")]
+        for seen in self.watched.values_mut() {
+            *seen = false;
+        }
+
+        // Reset which files are missing.
+        self.missing.clear();
+
+        let files = files
+            .into_iter()
+            .map(|path| (path, RecursiveMode::NonRecursive));
+        self.dirs = dirs.into_iter().collect();
+        let dirs = self
+            .dirs
+            .clone()
+            .into_iter()
+            .map(|path| (path, RecursiveMode::Recursive));
+
+        // Watch new paths that weren't watched yet.
+        for (path, mode) in files.chain(dirs) {
+            // We can't watch paths that don't exist with notify-rs. Instead, we
+            // add those to a `missing` set and fall back to manual poll
+            // watching.
+            if !path.exists() {
+                self.missing.insert(path);
+                continue;
+            }
+
+            // Watch the path if it's not already watched.
+            if !self.watched.contains_key(&path) {
+                self.watcher
+                    .watch(&path, mode)
+                    .map_err(|err| eco_format!("failed to watch {path:?} ({err})"))?;
+            }
+
+            // Mark the path as "seen" so that we don't unwatch it.
+            self.watched.insert(path, true);
+        }
+
+        // Unwatch old paths that don't need to be watched anymore.
+        self.watched.retain(|path, &mut seen| {
+            if !seen {
+                self.watcher.unwatch(path).ok();
+            }
+            seen
+        });
+
+        Ok(())
+    }
+
+    /// Wait until there is a change to a watched path and return the paths
+    /// that changed.
+    fn wait(&mut self) -> StrResult<Vec<PathBuf>> {
+        loop {
+            // Wait for an initial event. If there are missing files, we need to
+            // poll those regularly to check whether they are created, so we
+            // wait with a smaller timeout.
+            let first = self.rx.recv_timeout(if self.missing.is_empty() {
+                Duration::MAX
+            } else {
+                Self::POLL_INTERVAL
+            });
+
+            // Watch for file system events. If multiple events happen
+            // consecutively all within a certain duration, then they are
+            // bunched up without a recompile in-between. This helps against
+            // some editors' remove & move behavior. Events are also only
+            // watched until a certain point, to hinder a barrage of events from
+            // preventing recompilations.
+            let mut changed = Vec::new();
+            let batch_start = Instant::now();
+            for event in first
+                .into_iter()
+                .chain(iter::from_fn(|| {
+                    self.rx.recv_timeout(Self::BATCH_TIMEOUT).ok()
+                }))
+                .take_while(|_| batch_start.elapsed() <= Self::STARVE_TIMEOUT)
+            {
+                let event =
+                    event.map_err(|err| eco_format!("failed to watch dependencies ({err})"))?;
+
+                if !is_relevant_event_kind(&event.kind) {
+                    continue;
+                }
+
+                // Workaround for notify-rs' implicit unwatch on remove/rename
+                // (triggered by some editors when saving files) with the
+                // inotify backend. By keeping track of the potentially
+                // unwatched files, we can allow those we still depend on to be
+                // watched again later on.
+                if matches!(
+                    event.kind,
+                    notify::EventKind::Remove(notify::event::RemoveKind::File)
+                        | notify::EventKind::Modify(notify::event::ModifyKind::Name(
+                            notify::event::RenameMode::From
+                        ))
+                ) {
+                    for path in &event.paths {
+                        // Remove affected path from the watched map to restart
+                        // watching on it later again.
+                        self.watcher.unwatch(path).ok();
+                        self.watched.remove(path);
+                    }
+                }
+
+                // Don't recompile because an output file or a file we don't
+                // depend on changed.
+                changed.extend(
+                    event
+                        .paths
+                        .into_iter()
+                        .filter(|path| self.is_relevant_path(&event.kind, path)),
+                );
+            }
+
+            // If we found a relevant event or if any of the missing files now
+            // exists, stop waiting.
+            changed.extend(self.missing.iter().filter(|path| path.exists()).cloned());
+            if !changed.is_empty() {
+                return Ok(changed);
+            }
+        }
+    }
+
+    /// Whether an event for the path should trigger a recompilation.
+    fn is_relevant_path(&self, kind: &notify::EventKind, path: &Path) -> bool {
+        if self.is_output(path) {
+            return false;
+        }
+
+        // Content changes only matter for files we depend on, not for any
+        // file that happens to live in a watched directory.
+        if matches!(
+            kind,
+            notify::EventKind::Modify(
+                notify::event::ModifyKind::Any | notify::event::ModifyKind::Data(_)
+            )
+        ) {
+            return self.watched.contains_key(path)
+                || self.dirs.iter().any(|dir| path.starts_with(dir));
+        }
+
+        true
+    }
+
+    /// Whether the path is one of the outputs, the dependency file or the
+    /// recorded timings of the last compilation.
+    fn is_output(&self, path: &Path) -> bool {
+        self.outputs
+            .iter()
+            .chain(&self.deps)
+            .chain(&self.recording)
+            .any(|output| output == path || is_same_file(path, output).unwrap_or(false))
+    }
+}
+
+/// Make a path absolute like the paths of watch events, so that it can be
+/// recognized even after the file was removed.
+fn resolve(path: &Path) -> PathBuf {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    match dir.canonicalize() {
+        Ok(dir) => dir.join(name),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Whether a kind of watch event is relevant for compilation.
+fn is_relevant_event_kind(kind: &notify::EventKind) -> bool {
+    match kind {
+        notify::EventKind::Any => true,
+        notify::EventKind::Access(_) => false,
+        notify::EventKind::Create(_) => true,
+        notify::EventKind::Modify(kind) => match kind {
+            notify::event::ModifyKind::Any => true,
+            notify::event::ModifyKind::Data(_) => true,
+            notify::event::ModifyKind::Metadata(_) => false,
+            notify::event::ModifyKind::Name(_) => true,
+            notify::event::ModifyKind::Other => false,
+        },
+        notify::EventKind::Remove(_) => true,
+        notify::EventKind::Other => false,
+    }
+}
+
+/// The status in which the watcher can be.
+pub enum Status {
+    Compiling,
+    Success(Duration),
+    PartialSuccess(Duration),
+    Error,
+}
+
+impl Status {
+    /// Clear the terminal and render the status message.
+    pub fn print(&self, config: &CompileConfig) -> io::Result<()> {
+        let timestamp = chrono::offset::Local::now().format("%H:%M:%S");
+        let color = self.color();
+
+        let mut out = terminal::out();
+        out.clear_screen()?;
+
+        out.set_color(&color)?;
+        write!(out, "watching")?;
+        out.reset()?;
+        writeln!(out, " {}", config.input)?;
+
+        out.set_color(&color)?;
+        write!(out, "writing to")?;
+        out.reset()?;
+        writeln!(out, " {}", config.output)?;
+
+        writeln!(out)?;
+        writeln!(out, "[{timestamp}] {}", self.message())?;
+        writeln!(out)?;
+
+        out.flush()
+    }
+
+    fn message(&self) -> String {
+        match *self {
+            Self::Compiling => "compiling ...".into(),
+            Self::Success(duration) => {
+                format!("compiled successfully in {}", format_duration(duration))
+            }
+            Self::PartialSuccess(duration) => {
+                format!("compiled with warnings in {}", format_duration(duration))
+            }
+            Self::Error => "compiled with errors".into(),
+        }
+    }
+
+    fn color(&self) -> termcolor::ColorSpec {
+        let styles = term::Styles::default();
+        match self {
+            Self::Error => styles.header_error,
+            Self::PartialSuccess(_) => styles.header_warning,
+            _ => styles.header_note,
+        }
+    }
+}
+
+/// Emits a warning when trying to watch stdin.
+fn warn_watching_std(renderer: &Renderer) -> StrResult<()> {
+    let warning = warning!(
+        Span::detached(),
+        "cannot watch changes for stdin";
+        hint: "to recompile on changes, watch a regular file instead";
+        hint: "to compile once and exit, please use `compile` instead"
+    );
+    renderer.report(&[], &[warning])
+}
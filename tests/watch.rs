@@ -0,0 +1,210 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a compilation may take before the test gives up on it.
+const COMPILE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait for a compilation that must not happen.
+const QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+/// A running `watch` command whose status messages are collected.
+struct Watch {
+    child: Child,
+    status: Receiver<String>,
+}
+
+impl Watch {
+    /// Start watching the main file of a project.
+    fn start(dir: &Path, args: &[&str]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_main"))
+            .arg("watch")
+            .args(args)
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to start watching");
+
+        // The status is printed to stderr, a line per compilation.
+        let (tx, status) = channel();
+        let stderr = child.stderr.take().unwrap();
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                if line.contains("] compile") && !line.contains("compiling") {
+                    tx.send(line).ok();
+                }
+            }
+        });
+
+        Self { child, status }
+    }
+
+    /// Wait for the next compilation to finish and return its status.
+    fn next(&self) -> String {
+        match self.status.recv_timeout(COMPILE_TIMEOUT) {
+            Ok(status) => status,
+            Err(RecvTimeoutError::Timeout) => panic!("no compilation happened"),
+            Err(RecvTimeoutError::Disconnected) => panic!("the watcher exited"),
+        }
+    }
+
+    /// Check that nothing is compiled for a while.
+    fn assert_quiet(&self) {
+        let start = Instant::now();
+        if let Ok(status) = self.status.recv_timeout(QUIET_PERIOD) {
+            panic!(
+                "unexpected compilation after {:?}: {status}",
+                start.elapsed()
+            );
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// Wait until a file was written and return its contents. The dependencies
+/// are only written after the status of a compilation is printed.
+fn wait_for(path: &Path) -> Vec<u8> {
+    let start = Instant::now();
+    loop {
+        match fs::read(path) {
+            Ok(contents) if !contents.is_empty() => return contents,
+            _ => {}
+        }
+        assert!(
+            start.elapsed() < COMPILE_TIMEOUT,
+            "{} was not written",
+            path.display()
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Create a project whose main file imports another one.
+fn project() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("main.typ"),
+        "#import \"lib.typ\": greeting\n= #greeting\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("lib.typ"), "#let greeting = [Hello]\n").unwrap();
+    fs::write(dir.path().join("notes.txt"), "").unwrap();
+    dir
+}
+
+#[test]
+fn test_watch_recompiles_when_an_import_changes() {
+    let dir = project();
+    let watch = Watch::start(
+        dir.path(),
+        &["main.typ", "-o", "main.pdf", "--deps", "deps.json"],
+    );
+    assert!(watch.next().contains("compiled successfully"));
+    let first = fs::read(dir.path().join("main.pdf")).unwrap();
+
+    // Writing the output and the dependencies must not cause another
+    // compilation.
+    watch.assert_quiet();
+
+    fs::write(dir.path().join("lib.typ"), "#let greeting = [Goodbye]\n").unwrap();
+    assert!(watch.next().contains("compiled successfully"));
+    assert_ne!(fs::read(dir.path().join("main.pdf")).unwrap(), first);
+    let deps = String::from_utf8(wait_for(&dir.path().join("deps.json"))).unwrap();
+    assert!(deps.contains("lib.typ"));
+    watch.assert_quiet();
+}
+
+#[test]
+fn test_watch_ignores_changes_to_its_outputs() {
+    let dir = project();
+    let watch = Watch::start(
+        dir.path(),
+        &["main.typ", "-o", "main.pdf", "--deps", "deps.json"],
+    );
+    assert!(watch.next().contains("compiled successfully"));
+
+    // Replacing the output and the dependencies is ignored, even though new
+    // files in the project root are not.
+    for file in ["main.pdf", "deps.json"] {
+        wait_for(&dir.path().join(file));
+        fs::remove_file(dir.path().join(file)).unwrap();
+        fs::write(dir.path().join(file), "").unwrap();
+    }
+    watch.assert_quiet();
+
+    // Neither does a file that is not a dependency.
+    fs::write(dir.path().join("notes.txt"), "todo").unwrap();
+    watch.assert_quiet();
+}
+
+#[test]
+fn test_watch_ignores_its_dependencies_after_an_error() {
+    let dir = project();
+    fs::write(
+        dir.path().join("lib.typ"),
+        "#let greeting = [Hello]\n#panic()\n",
+    )
+    .unwrap();
+    let watch = Watch::start(
+        dir.path(),
+        &["main.typ", "-o", "main.pdf", "--deps", "deps.json"],
+    );
+    assert!(watch.next().contains("compiled with errors"));
+
+    let deps = dir.path().join("deps.json");
+    wait_for(&deps);
+    fs::remove_file(&deps).unwrap();
+    fs::write(&deps, "").unwrap();
+    watch.assert_quiet();
+}
+
+#[test]
+fn test_watch_requires_numbered_timings() {
+    let dir = project();
+    let output = Command::new(env!("CARGO_BIN_EXE_main"))
+        .args(["watch", "main.typ", "--timings", "timings.json"])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("without `{n}` in the path"), "{stderr}");
+}
+
+#[test]
+fn test_watch_keeps_going_when_the_fonts_cannot_be_found() {
+    let dir = project();
+    let manifest = dir.path().join("fonts.toml");
+    fs::write(&manifest, "embedded = true\n").unwrap();
+    let watch = Watch::start(
+        dir.path(),
+        &[
+            "main.typ",
+            "-o",
+            "main.pdf",
+            "--font-manifest",
+            "fonts.toml",
+        ],
+    );
+    assert!(watch.next().contains("compiled successfully"));
+    watch.assert_quiet();
+
+    // The error is reported like a failed compilation, and the watcher
+    // recovers once the fonts are fixed.
+    fs::write(&manifest, "embedded = true\nfonts = [\"missing\"]\n").unwrap();
+    assert!(watch.next().contains("compiled with errors"));
+    watch.assert_quiet();
+    fs::write(&manifest, "embedded = true\n").unwrap();
+    assert!(watch.next().contains("compiled successfully"));
+}